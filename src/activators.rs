@@ -1,19 +1,40 @@
 #![allow(dead_code)]
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Type {
     Sigmoid,
     Tanh,
     Relu,
+    Sine,
+    Gaussian,
+    Identity,
     Custom
 }
 
+/// Activations a neuron can evolve, see `EvoNetConfig::activation_mutation_rate`
+pub const EVOLVABLE: [Type; 6] = [Type::Sigmoid, Type::Tanh, Type::Relu, Type::Sine, Type::Gaussian, Type::Identity];
+
+impl Type {
+    /// Function of the activation, `None` for `Custom`
+    pub fn function(&self) -> Option<fn(f64) -> f64> {
+        match self {
+            Type::Sigmoid => Some(sigm),
+            Type::Tanh => Some(tanh),
+            Type::Relu => Some(relu),
+            Type::Sine => Some(sine),
+            Type::Gaussian => Some(gauss),
+            Type::Identity => Some(identity),
+            Type::Custom => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct ActivationContainer {
     pub func: fn(f64) -> f64
 }
 
-pub fn sigm(x: f64) -> f64{ 1.0/(1.0 + x.exp()) }
+pub fn sigm(x: f64) -> f64{ 1.0/(1.0 + (-x).exp()) }
 
 pub fn tanh(x: f64) -> f64{
     x.tanh()
@@ -21,4 +42,16 @@ pub fn tanh(x: f64) -> f64{
 
 pub fn relu(x: f64) -> f64{
     f64::max(0.0, x)
-}
\ No newline at end of file
+}
+
+pub fn sine(x: f64) -> f64{
+    x.sin()
+}
+
+pub fn gauss(x: f64) -> f64{
+    (-x * x).exp()
+}
+
+pub fn identity(x: f64) -> f64{
+    x
+}
@@ -0,0 +1,374 @@
+use std::fmt::Display;
+use rand::{rngs::StdRng, Rng};
+
+use crate::{activators, evonet::EvoNet, evotrainer::{crossover::CrossoverOperator, evotrainer::HasFitness, initialization::WeightInit, mutation::MutationOperator, speciation::DistanceMetric}, genome::{Genome, Network}};
+
+/// Channels, height and width of a feature map, stored channel by channel, row by row
+pub type Shape = (usize, usize, usize);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoolKind {
+    #[default]
+    Max,
+    Average,
+}
+
+/// One entry of a `ConvNet` description
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LayerSpec {
+    /// tanh convolution with square kernels, zero padded (channels, kernel size, stride, padding)
+    Conv(usize, usize, usize, usize),
+    /// Pooling over square windows of each channel (kind, window size, stride)
+    Pool(PoolKind, usize, usize),
+    /// Dense layer of the given width, hidden ones tanh and the last one linear.
+    /// A single dense layer is tanh, like an `EvoNet` without hidden layers
+    Dense(usize),
+}
+
+/// Network description shared by a population of `ConvNet`s
+#[derive(Clone, Debug, Default)]
+pub struct ConvNetConfig {
+    pub input_shape: Shape,
+    /// Convolutions and pooling first, then at least one dense layer. The trainer
+    /// architecture has to start with the input size and end with the last dense width.
+    /// Empty uses the trainer architecture as dense layers over a flat input
+    pub layers: Vec<LayerSpec>,
+}
+
+#[derive(Clone)]
+enum FeatureLayer {
+    Conv {
+        kernel: usize,
+        stride: usize,
+        padding: usize,
+        /// Bias and kernel weights of each output channel, input channel by channel
+        weights: Vec<Vec<f64>>,
+        input: Shape,
+        output: Shape,
+    },
+    Pool {
+        kind: PoolKind,
+        size: usize,
+        stride: usize,
+        input: Shape,
+        output: Shape,
+    },
+}
+
+impl FeatureLayer {
+    fn output(&self) -> Shape {
+        match self {
+            FeatureLayer::Conv { output, .. } | FeatureLayer::Pool { output, .. } => *output,
+        }
+    }
+
+    fn forward(&self, x: &[f64]) -> Vec<f64> {
+        match self {
+            FeatureLayer::Conv { kernel, stride, padding, weights, input, output } => {
+                let (channels, height, width) = *input;
+                let mut y = Vec::with_capacity(output.0 * output.1 * output.2);
+                for w in weights.iter() {
+                    for oy in 0..output.1 {
+                        for ox in 0..output.2 {
+                            let mut sum = w[0];
+                            for c in 0..channels {
+                                for ky in 0..*kernel {
+                                    for kx in 0..*kernel {
+                                        let iy = (oy * stride + ky) as isize - *padding as isize;
+                                        let ix = (ox * stride + kx) as isize - *padding as isize;
+                                        if iy < 0 || ix < 0 || iy as usize >= height || ix as usize >= width {
+                                            continue;
+                                        }
+                                        sum += w[1 + (c * kernel + ky) * kernel + kx] * x[(c * height + iy as usize) * width + ix as usize];
+                                    }
+                                }
+                            }
+                            y.push(activators::tanh(sum));
+                        }
+                    }
+                }
+                y
+            },
+            FeatureLayer::Pool { kind, size, stride, input, output } => {
+                let (_, height, width) = *input;
+                let mut y = Vec::with_capacity(output.0 * output.1 * output.2);
+                for c in 0..output.0 {
+                    for oy in 0..output.1 {
+                        for ox in 0..output.2 {
+                            let window = (0..*size).flat_map(|ky| (0..*size).map(move |kx| (ky, kx)))
+                                .map(|(ky, kx)| x[(c * height + oy * stride + ky) * width + ox * stride + kx]);
+                            y.push(match kind {
+                                PoolKind::Max => window.fold(f64::MIN, f64::max),
+                                PoolKind::Average => window.sum::<f64>() / (size * size) as f64,
+                            });
+                        }
+                    }
+                }
+                y
+            },
+        }
+    }
+}
+
+/// Whether a window of the given size and stride can slide along one axis
+fn window_fits(input: usize, window: usize, stride: usize, padding: usize) -> bool {
+    window > 0 && window <= input + 2 * padding && stride > 0
+}
+
+/// Size of a convolution or pooling output along one axis
+fn output_size(input: usize, window: usize, stride: usize, padding: usize) -> usize {
+    assert!(window_fits(input, window, stride, padding), "window doesn't fit the feature map");
+    (input + 2 * padding - window) / stride + 1
+}
+
+/// Convolutions and pooling over a channel, row, column ordered input,
+/// followed by a dense `EvoNet` over the flattened features
+#[derive(Clone)]
+pub struct ConvNet {
+    input_shape: Shape,
+    features: Vec<FeatureLayer>,
+    dense: EvoNet,
+    fitness: f64,
+}
+
+impl ConvNet {
+    pub fn new(input_shape: Shape, layers: &[LayerSpec]) -> ConvNet {
+        let mut features = Vec::new();
+        let mut shape = input_shape;
+        let mut dense = vec![];
+
+        for spec in layers {
+            match *spec {
+                LayerSpec::Dense(width) => {
+                    if dense.is_empty() {
+                        dense.push(shape.0 * shape.1 * shape.2);
+                    }
+                    dense.push(width);
+                    continue;
+                },
+                _ if !dense.is_empty() => panic!("convolutions and pooling must come before the dense layers"),
+                LayerSpec::Conv(channels, kernel, stride, padding) => {
+                    let output = (channels, output_size(shape.1, kernel, stride, padding), output_size(shape.2, kernel, stride, padding));
+                    let weights = (0..channels).map(|_| {
+                        (0..shape.0 * kernel * kernel + 1).map(|_| 2f64 * rand::random::<f64>() - 1f64).collect()
+                    }).collect();
+                    features.push(FeatureLayer::Conv { kernel, stride, padding, weights, input: shape, output });
+                },
+                LayerSpec::Pool(kind, size, stride) => {
+                    let output = (shape.0, output_size(shape.1, size, stride, 0), output_size(shape.2, size, stride, 0));
+                    features.push(FeatureLayer::Pool { kind, size, stride, input: shape, output });
+                },
+            }
+            shape = features[features.len() - 1].output();
+        }
+        assert!(!dense.is_empty(), "a convnet needs at least one dense layer");
+
+        ConvNet {
+            input_shape,
+            features,
+            dense: EvoNet::new(&dense),
+            fitness: 0.0,
+        }
+    }
+
+    /// Recombines the weights of parents sharing a description
+    pub fn from_many_parents(parents: &[&ConvNet], fitnesses: &[f64], operator: CrossoverOperator) -> ConvNet {
+        let weights: Vec<Vec<f64>> = parents.iter().map(|p| p.get_weights()).collect();
+        let mut nn = parents[0].clone();
+        nn.set_weights(&operator.recombine(&weights, fitnesses));
+        nn.fitness = 0.0;
+        nn
+    }
+
+    pub fn get_input_shape(&self) -> Shape {
+        self.input_shape
+    }
+
+    /// Shape of the feature map after each convolution and pooling layer
+    pub fn get_feature_shapes(&self) -> Vec<Shape> {
+        self.features.iter().map(|f| f.output()).collect()
+    }
+
+    /// Input count, then the widths of the dense layers
+    pub fn get_architecture(&self) -> Vec<usize> {
+        let mut architecture = self.dense.get_architecture();
+        architecture[0] = self.input_shape.0 * self.input_shape.1 * self.input_shape.2;
+        architecture
+    }
+
+    /// Amount of kernel and dense weights, including biases
+    pub fn weight_count(&self) -> usize {
+        self.features.iter().fold(self.dense.weight_count(), |sum, f| match f {
+            FeatureLayer::Conv { weights, .. } => sum + weights.iter().fold(0, |sum, w| sum + w.len()),
+            FeatureLayer::Pool { .. } => sum,
+        })
+    }
+
+    /// Flattens the kernel weights layer by layer, followed by the dense weights
+    pub fn get_weights(&self) -> Vec<f64> {
+        let mut all = Vec::with_capacity(self.weight_count());
+        self.features.iter().for_each(|f| if let FeatureLayer::Conv { weights, .. } = f {
+            weights.iter().for_each(|w| all.extend_from_slice(w));
+        });
+        all.extend(self.dense.get_weights());
+        all
+    }
+
+    /// Overwrites the weights from a flat vector in the order of `get_weights`
+    pub fn set_weights(&mut self, all: &[f64]) {
+        let mut position: usize = 0;
+        self.features.iter_mut().for_each(|f| if let FeatureLayer::Conv { weights, .. } = f {
+            weights.iter_mut().for_each(|w| {
+                let len = w.len();
+                w.copy_from_slice(&all[position..position + len]);
+                position += len;
+            });
+        });
+        self.dense.set_weights(&all[position..]);
+    }
+
+    /// Redraws the weights, `schemes[i]` for the i-th convolution or dense layer
+    /// in the order of the description. Kernels draw one row of bias and
+    /// kernel weights per output channel. Layers past the end of `schemes`
+    /// use `WeightInit::default()`
+    pub fn initialize_weights<R: Rng>(&mut self, schemes: &[WeightInit], rng: &mut R) {
+        let mut convolutions = 0;
+        self.features.iter_mut().for_each(|f| if let FeatureLayer::Conv { weights, .. } = f {
+            schemes.get(convolutions).copied().unwrap_or_default().initialize(weights, rng);
+            convolutions += 1;
+        });
+        self.dense.initialize_weights(schemes.get(convolutions..).unwrap_or(&[]), rng);
+    }
+
+    pub fn distance_with(&self, other: &ConvNet, metric: DistanceMetric) -> f64 {
+        metric.measure(&self.get_weights(), &other.get_weights())
+    }
+
+    pub fn set_fitness(&mut self, ft: f64) {
+        self.fitness = ft;
+    }
+
+    /// Perturbs each kernel and dense weight with probability `frequency` using the given operator
+    pub fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        let mut weights = self.get_weights();
+        operator.mutate(&mut weights, frequency);
+        self.set_weights(&weights);
+    }
+
+    pub fn calc(&mut self, x: &[f64]) -> &[f64] {
+        let features = self.features.iter().fold(x.to_vec(), |y, f| f.forward(&y));
+        self.dense.calc(&features)
+    }
+}
+
+impl Display for ConvNet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut net_str = String::from("");
+        self.features.iter().for_each(|l| match l {
+            FeatureLayer::Conv { kernel, stride, padding, weights, output, .. } => {
+                net_str.push_str(&format!("conv {}x{} stride {} padding {} -> {:?}\n", kernel, kernel, stride, padding, output));
+                weights.iter().for_each(|w| {
+                    let w: Vec<String> = w.iter().map(|v| v.to_string()).collect();
+                    net_str.push_str(&format!("[{}]\n", w.join(", ")));
+                });
+            },
+            FeatureLayer::Pool { kind, size, stride, output, .. } => {
+                net_str.push_str(&format!("pool {:?} {}x{} stride {} -> {:?}\n", kind, size, size, stride, output));
+            },
+        });
+
+        write!(f, "input: {:?}\n{}dense {}", self.input_shape, net_str, self.dense)
+    }
+}
+
+impl HasFitness for ConvNet {
+    fn get_fitness(&self) -> f64 {
+        self.fitness
+    }
+}
+
+impl Network for ConvNet {
+    fn calc(&mut self, x: &[f64]) -> &[f64] {
+        ConvNet::calc(self, x)
+    }
+}
+
+impl Genome for ConvNet {
+    type Config = ConvNetConfig;
+
+    fn spawn(architecture: &[usize], config: &ConvNetConfig) -> Self {
+        if config.layers.is_empty() {
+            let dense: Vec<LayerSpec> = architecture[1..].iter().map(|n| LayerSpec::Dense(*n)).collect();
+            return ConvNet::new((1, 1, architecture[0]), &dense);
+        }
+        ConvNet::new(config.input_shape, &config.layers)
+    }
+
+    fn validate_config(architecture: &[usize], config: &ConvNetConfig) -> Result<(), String> {
+        if config.layers.is_empty() {
+            return Ok(());
+        }
+        let (channels, height, width) = config.input_shape;
+        if architecture.first() != Some(&(channels * height * width)) {
+            return Err(String::from("convnet input_shape must hold as many values as the first entry of the architecture"));
+        }
+
+        // Same walk as `ConvNet::new`, reporting what it would panic on
+        let mut shape = config.input_shape;
+        let mut dense = false;
+        for spec in config.layers.iter() {
+            let (channels, window, stride, padding) = match *spec {
+                LayerSpec::Dense(0) => return Err(String::from("convnet dense layers must be wider than 0")),
+                LayerSpec::Dense(_) => {
+                    dense = true;
+                    continue;
+                },
+                _ if dense => return Err(String::from("convnet convolutions and pooling must come before the dense layers")),
+                LayerSpec::Conv(0, ..) => return Err(String::from("convnet convolutions need at least one channel")),
+                LayerSpec::Conv(channels, kernel, stride, padding) => (channels, kernel, stride, padding),
+                LayerSpec::Pool(_, size, stride) => (shape.0, size, stride, 0),
+            };
+            if !window_fits(shape.1, window, stride, padding) || !window_fits(shape.2, window, stride, padding) {
+                return Err(String::from("convnet kernels and pooling windows must be non-empty, fit the feature map and have a stride greater than 0"));
+            }
+            shape = (channels, output_size(shape.1, window, stride, padding), output_size(shape.2, window, stride, padding));
+        }
+
+        match config.layers.last() {
+            Some(LayerSpec::Dense(outputs)) if Some(outputs) == architecture.last() => Ok(()),
+            Some(LayerSpec::Dense(_)) => Err(String::from("convnet last dense layer must be as wide as the last entry of the architecture")),
+            _ => Err(String::from("convnet layers must end with a dense layer")),
+        }
+    }
+
+    fn from_many_parents(parents: &[&Self], fitnesses: &[f64], operator: CrossoverOperator) -> Self {
+        ConvNet::from_many_parents(parents, fitnesses, operator)
+    }
+
+    fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        ConvNet::mutate_with(self, frequency, operator)
+    }
+
+    fn distance_with(&self, other: &Self, metric: DistanceMetric) -> f64 {
+        ConvNet::distance_with(self, other, metric)
+    }
+
+    fn set_fitness(&mut self, ft: f64) {
+        ConvNet::set_fitness(self, ft)
+    }
+
+    fn get_architecture(&self) -> Vec<usize> {
+        ConvNet::get_architecture(self)
+    }
+
+    fn weight_layer_count(architecture: &[usize], config: &ConvNetConfig) -> Option<usize> {
+        if config.layers.is_empty() {
+            return Some(architecture.len().saturating_sub(1));
+        }
+        Some(config.layers.iter().filter(|l| !matches!(l, LayerSpec::Pool(..))).count())
+    }
+
+    fn initialize_weights(&mut self, schemes: &[WeightInit], rng: &mut StdRng) {
+        ConvNet::initialize_weights(self, schemes, rng)
+    }
+}
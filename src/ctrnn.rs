@@ -0,0 +1,290 @@
+use std::fmt::Display;
+
+use crate::{activators, evotrainer::{crossover::CrossoverOperator, evotrainer::HasFitness, mutation::MutationOperator, speciation::DistanceMetric}, genome::{Genome, Network}};
+
+/// Numerical scheme advancing the neuron states
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Integrator {
+    #[default]
+    Euler,
+    RungeKutta4,
+}
+
+/// Integration settings shared by a population of `Ctrnn`s
+#[derive(Clone, Copy, Debug)]
+pub struct CtrnnConfig {
+    pub integrator: Integrator,
+    /// Time advanced per integration step, also the smallest time constant
+    pub step_size: f64,
+    /// Integration steps per `calc` call, the input is held during them
+    pub substeps: usize,
+}
+
+impl Default for CtrnnConfig {
+    fn default() -> Self {
+        CtrnnConfig {
+            integrator: Integrator::Euler,
+            step_size: 0.1,
+            substeps: 1,
+        }
+    }
+}
+
+/// Fully connected continuous-time recurrent network following
+/// `tau_i dy_i/dt = -y_i + sum_j w_ji sigm(g_j (y_j + b_j)) + sum_k v_ik x_k`.
+/// The architecture is `[inputs, neurons, outputs]` or `[inputs, outputs]`,
+/// the outputs are the firing rates of the last neurons
+#[derive(Clone)]
+pub struct Ctrnn {
+    /// Incoming weights of each neuron from every neuron
+    weights: Vec<Vec<f64>>,
+    /// Incoming weights of each neuron from the inputs
+    input_weights: Vec<Vec<f64>>,
+    biases: Vec<f64>,
+    gains: Vec<f64>,
+    time_constants: Vec<f64>,
+    state: Vec<f64>,
+    outputs: usize,
+    output: Vec<f64>,
+    config: CtrnnConfig,
+    fitness: f64,
+}
+
+impl Ctrnn {
+    pub fn new(architecture: &[usize], config: &CtrnnConfig) -> Ctrnn {
+        let inputs = architecture[0];
+        let outputs = architecture[architecture.len() - 1];
+        let neurons = if architecture.len() > 2 { architecture[1] } else { outputs };
+        assert!(outputs <= neurons, "a ctrnn can't have more outputs than neurons");
+
+        let uniform = || 2f64 * rand::random::<f64>() - 1f64;
+        let mut net = Ctrnn {
+            weights: (0..neurons).map(|_| (0..neurons).map(|_| uniform()).collect()).collect(),
+            input_weights: (0..neurons).map(|_| (0..inputs).map(|_| uniform()).collect()).collect(),
+            biases: (0..neurons).map(|_| uniform()).collect(),
+            gains: vec![1.0; neurons],
+            time_constants: (0..neurons).map(|_| 1.0 + rand::random::<f64>()).collect(),
+            state: vec![0.0; neurons],
+            outputs,
+            output: vec![0.0; outputs],
+            config: *config,
+            fitness: 0.0,
+        };
+        net.clamp_time_constants();
+        net
+    }
+
+    /// Recombines parents of the same architecture into a child
+    pub fn from_many_parents(parents: &[&Ctrnn], fitnesses: &[f64], operator: CrossoverOperator) -> Ctrnn {
+        let genes: Vec<Vec<f64>> = parents.iter().map(|p| p.get_genes()).collect();
+        let mut nn = parents[0].clone();
+        nn.set_genes(&operator.recombine(&genes, fitnesses));
+        nn.fitness = 0.0;
+        nn.reset_state();
+        nn
+    }
+
+    pub fn get_architecture(&self) -> Vec<usize> {
+        vec![self.input_weights[0].len(), self.state.len(), self.outputs]
+    }
+
+    pub fn get_config(&self) -> CtrnnConfig {
+        self.config
+    }
+
+    /// Current neuron states `y`
+    pub fn get_state(&self) -> &[f64] {
+        &self.state
+    }
+
+    pub fn get_time_constants(&self) -> &[f64] {
+        &self.time_constants
+    }
+
+    pub fn get_biases(&self) -> &[f64] {
+        &self.biases
+    }
+
+    pub fn get_gains(&self) -> &[f64] {
+        &self.gains
+    }
+
+    pub fn gene_count(&self) -> usize {
+        let neurons = self.state.len();
+        neurons * (neurons + self.input_weights[0].len() + 3)
+    }
+
+    /// Flattens the weights, input weights, biases, gains and time constants in that order
+    pub fn get_genes(&self) -> Vec<f64> {
+        let mut genes = Vec::with_capacity(self.gene_count());
+        self.weights.iter().for_each(|n| genes.extend_from_slice(n));
+        self.input_weights.iter().for_each(|n| genes.extend_from_slice(n));
+        genes.extend_from_slice(&self.biases);
+        genes.extend_from_slice(&self.gains);
+        genes.extend_from_slice(&self.time_constants);
+        genes
+    }
+
+    /// Overwrites the genes from a flat vector in the order of `get_genes`,
+    /// time constants are kept at or above the step size
+    pub fn set_genes(&mut self, genes: &[f64]) {
+        let mut position: usize = 0;
+        let mut take = |target: &mut [f64]| {
+            let len = target.len();
+            target.copy_from_slice(&genes[position..position + len]);
+            position += len;
+        };
+        self.weights.iter_mut().for_each(|n| take(n));
+        self.input_weights.iter_mut().for_each(|n| take(n));
+        take(&mut self.biases);
+        take(&mut self.gains);
+        take(&mut self.time_constants);
+        self.clamp_time_constants();
+    }
+
+    pub fn distance_with(&self, other: &Ctrnn, metric: DistanceMetric) -> f64 {
+        metric.measure(&self.get_genes(), &other.get_genes())
+    }
+
+    pub fn set_fitness(&mut self, ft: f64) {
+        self.fitness = ft;
+    }
+
+    /// Perturbs each gene, time constants and gains included,
+    /// with probability `frequency` using the given operator
+    pub fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        let mut genes = self.get_genes();
+        operator.mutate(&mut genes, frequency);
+        self.set_genes(&genes);
+    }
+
+    pub fn reset_state(&mut self) {
+        self.state.iter_mut().for_each(|s| *s = 0.0);
+        self.output.iter_mut().for_each(|o| *o = 0.0);
+    }
+
+    /// Holds the input for `substeps` integration steps and returns the
+    /// firing rates of the output neurons
+    #[allow(non_snake_case)]
+    pub fn calc(&mut self, X: &[f64]) -> &[f64] {
+        let external: Vec<f64> = self.input_weights.iter().map(|n| {
+            n.iter().zip(X.iter()).fold(0.0, |sum, (w, x)| sum + w * x)
+        }).collect();
+        let h = self.config.step_size;
+
+        for _ in 0..self.config.substeps {
+            self.state = match self.config.integrator {
+                Integrator::Euler => {
+                    let k = self.derivative(&self.state, &external);
+                    self.state.iter().zip(k.iter()).map(|(y, d)| y + h * d).collect()
+                }
+                Integrator::RungeKutta4 => {
+                    let shifted = |k: &[f64], scale: f64| -> Vec<f64> {
+                        self.state.iter().zip(k.iter()).map(|(y, d)| y + scale * d).collect()
+                    };
+                    let k1 = self.derivative(&self.state, &external);
+                    let k2 = self.derivative(&shifted(&k1, h / 2.0), &external);
+                    let k3 = self.derivative(&shifted(&k2, h / 2.0), &external);
+                    let k4 = self.derivative(&shifted(&k3, h), &external);
+                    (0..self.state.len()).map(|i| {
+                        self.state[i] + h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i])
+                    }).collect()
+                }
+            };
+        }
+
+        let first_output = self.state.len() - self.outputs;
+        let rates = self.firing_rates(&self.state);
+        self.output.copy_from_slice(&rates[first_output..]);
+        &self.output
+    }
+
+    fn firing_rates(&self, state: &[f64]) -> Vec<f64> {
+        (0..state.len()).map(|j| activators::sigm(self.gains[j] * (state[j] + self.biases[j]))).collect()
+    }
+
+    fn derivative(&self, state: &[f64], external: &[f64]) -> Vec<f64> {
+        let rates = self.firing_rates(state);
+        (0..state.len()).map(|i| {
+            let recurrent = self.weights[i].iter().zip(rates.iter()).fold(0.0, |sum, (w, r)| sum + w * r);
+            (-state[i] + recurrent + external[i]) / self.time_constants[i]
+        }).collect()
+    }
+
+    fn clamp_time_constants(&mut self) {
+        let min = self.config.step_size;
+        self.time_constants.iter_mut().for_each(|t| *t = t.max(min));
+    }
+}
+
+impl Display for Ctrnn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut net_str = String::from("");
+        (0..self.state.len()).for_each(|i| {
+            let weights: Vec<String> = self.weights[i].iter().chain(self.input_weights[i].iter()).map(|w| w.to_string()).collect();
+            net_str.push_str(&format!("tau: {} bias: {} gain: {} [{}]\n", self.time_constants[i], self.biases[i], self.gains[i], weights.join(", ")));
+        });
+
+        write!(f, "fitness: {}\n{}", self.fitness, net_str)
+    }
+}
+
+impl HasFitness for Ctrnn {
+    fn get_fitness(&self) -> f64 {
+        self.fitness
+    }
+}
+
+impl Network for Ctrnn {
+    fn calc(&mut self, x: &[f64]) -> &[f64] {
+        Ctrnn::calc(self, x)
+    }
+
+    fn reset_state(&mut self) {
+        Ctrnn::reset_state(self)
+    }
+}
+
+impl Genome for Ctrnn {
+    type Config = CtrnnConfig;
+
+    fn spawn(architecture: &[usize], config: &CtrnnConfig) -> Self {
+        Ctrnn::new(architecture, config)
+    }
+
+    fn validate_config(architecture: &[usize], config: &CtrnnConfig) -> Result<(), String> {
+        match architecture {
+            [_, _] => {},
+            [_, neurons, outputs] if outputs <= neurons => {},
+            [_, _, _] => return Err(String::from("ctrnn can't have more outputs than neurons")),
+            _ => return Err(String::from("ctrnn architecture must be [inputs, neurons, outputs] or [inputs, outputs]")),
+        }
+        if config.step_size <= 0.0 {
+            return Err(String::from("ctrnn step_size must be greater than 0.0"));
+        }
+        if config.substeps == 0 {
+            return Err(String::from("ctrnn substeps must be greater than 0"));
+        }
+        Ok(())
+    }
+
+    fn from_many_parents(parents: &[&Self], fitnesses: &[f64], operator: CrossoverOperator) -> Self {
+        Ctrnn::from_many_parents(parents, fitnesses, operator)
+    }
+
+    fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        Ctrnn::mutate_with(self, frequency, operator)
+    }
+
+    fn distance_with(&self, other: &Self, metric: DistanceMetric) -> f64 {
+        Ctrnn::distance_with(self, other, metric)
+    }
+
+    fn set_fitness(&mut self, ft: f64) {
+        Ctrnn::set_fitness(self, ft)
+    }
+
+    fn get_architecture(&self) -> Vec<usize> {
+        Ctrnn::get_architecture(self)
+    }
+}
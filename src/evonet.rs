@@ -1,19 +1,22 @@
 use std::fmt::Display;
-use rand::{thread_rng, Rng};
-use rand_distr::StandardNormal;
+use rand::{rngs::StdRng, thread_rng, Rng};
 
-use crate::{activators::{self, ActivationContainer}, evotrainer::evotrainer::HasFitness};
+use crate::{activators::{self, ActivationContainer}, evotrainer::{crossover::{parent_shares, CrossoverOperator}, evotrainer::HasFitness, initialization::WeightInit, mutation::MutationOperator, speciation::DistanceMetric}, genome::{Genome, Network}};
 
 #[derive(Clone)]
 struct Layer {
     v: Vec<f64>,
     y: Vec<f64>,
     w: Vec<Vec<f64>>,
+    /// Activation gene of each neuron, unused in the output layer
+    a: Vec<activators::Type>,
+    /// Adds the previous layer's outputs, neuron by neuron
+    r: bool,
 }
 
 impl Layer {
-    fn new(amount: usize, input: usize) -> Layer {
-        let mut nl = Layer {v: vec![], y: vec![], w: Vec::new()};
+    fn new(amount: usize, input: usize, act: activators::Type) -> Layer {
+        let mut nl = Layer {v: vec![], y: vec![], w: Vec::new(), a: vec![act; amount], r: false};
         let mut v: Vec<f64>;
         for _ in 0..amount {
             nl.y.push(0.0);
@@ -26,27 +29,17 @@ impl Layer {
 
             nl.w.push(v);
         }
-        return nl;
+        nl
     }
 
-    fn new_from_parents(l1: &Layer, l2: &Layer, p1_fitness: f64, p2_fitness: f64) -> Layer {
-        let mut nl = Layer {
-            v: l1.v.clone(),
-            y: l1.y.clone(),
-            w: l1.w.clone(),
-        };
-
-        let mut rng = thread_rng();
-        let fitness_ratio = p1_fitness / (p1_fitness + p2_fitness);
-
-        for i in 0..nl.w.len() {
-            for j in 0..nl.w[i].len() {
-                if rng.gen_range(0.0..=1.0) > fitness_ratio {
-                    nl.w[i][j] = l2.w[i][j];
-                }
-            }
+    /// Passes its inputs through unchanged, up to the activation
+    fn identity(width: usize, act: activators::Type) -> Layer {
+        let mut nl = Layer {v: vec![0.0; width], y: vec![0.0; width], w: Vec::with_capacity(width), a: vec![act; width], r: false};
+        for i in 0..width {
+            let mut v = vec![0.0; width + 1];
+            v[i + 1] = 1.0;
+            nl.w.push(v);
         }
-
         nl
     }
 }
@@ -66,88 +59,327 @@ impl Display for Layer {
     }
 }
 
+/// Connection topology and the rates of the structural and activation mutations
+/// applied by `EvoNet::mutate_with`. Structural rates are the chance per mutation
+/// of changing the shape once
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EvoNetConfig {
+    /// Adds a neuron with zero outgoing weights to a random hidden layer
+    pub add_neuron_rate: f64,
+    /// Removes a random neuron from a hidden layer with more than one
+    pub remove_neuron_rate: f64,
+    /// Inserts an identity initialised layer at a random depth
+    pub add_layer_rate: f64,
+    /// Removes a random hidden layer
+    pub remove_layer_rate: f64,
+    /// Chance per hidden neuron and mutation of drawing a new activation
+    /// from `activators::EVOLVABLE`
+    pub activation_mutation_rate: f64,
+    /// Feeds the raw inputs to every layer besides the first, their weights
+    /// follow the previous layer's in each neuron
+    pub input_skips: bool,
+    /// Adds the previous layer's outputs to every layer built as wide as it,
+    /// besides the first. The links are fixed when the net is built, later
+    /// structural mutations keep them and pair the neurons up to the narrower width
+    pub residual: bool,
+}
+
 #[derive(Clone)]
 pub struct EvoNet {
     layers: Vec<Layer>,
     act_type: activators::Type,
     act: ActivationContainer,
+    config: EvoNetConfig,
     fitness: f64
 }
 
 impl EvoNet {
     pub fn new(architecture: &[usize]) -> EvoNet {
+        Self::with_config(architecture, &EvoNetConfig::default())
+    }
+
+    pub fn with_config(architecture: &[usize], config: &EvoNetConfig) -> EvoNet {
         let mut nn = EvoNet {
             layers: Vec::new(),
             act: ActivationContainer{ func: activators::tanh },
             act_type: activators::Type::Tanh,
+            config: *config,
             fitness: 0.0,
         };
 
         for i in 1..architecture.len() {
-            nn.layers.push(Layer::new(architecture[i], architecture[i - 1]))
+            let skips = if config.input_skips && i > 1 { architecture[0] } else { 0 };
+            let mut layer = Layer::new(architecture[i], architecture[i - 1] + skips, nn.act_type);
+            layer.r = config.residual && i > 1 && architecture[i - 1] == architecture[i];
+            nn.layers.push(layer);
         }
 
-        return nn;
+        nn
     }
 
     pub fn from_parents(p1: &EvoNet, p2: &EvoNet, p1_fitness: f64, p2_fitness: f64) -> EvoNet {
-        let mut nn = EvoNet {
-            layers: Vec::new(),
-            act: p1.act,
-            act_type: p1.act_type,
-            fitness: 0.0
+        Self::from_many_parents(&[p1, p2], &[p1_fitness, p2_fitness], CrossoverOperator::FitnessWeighted)
+    }
+
+    /// Recombines any number of parents into a child shaped like the fittest parent.
+    /// Parents of other shapes contribute the weights they share with it, hidden
+    /// layers aligned by depth and output layers with each other
+    pub fn from_many_parents(parents: &[&EvoNet], fitnesses: &[f64], operator: CrossoverOperator) -> EvoNet {
+        let mut rng = thread_rng();
+        let fittest = (0..parents.len()).reduce(|best, p| if fitnesses[p] > fitnesses[best] { p } else { best }).unwrap();
+        let mut nn = parents[fittest].clone();
+        nn.fitness = 0.0;
+        let depth = nn.layers.len();
+
+        let parent_weights = parent_shares(fitnesses);
+
+        let mut cuts: Vec<usize> = match operator {
+            CrossoverOperator::Diagonal => (1..parents.len()).map(|_| rng.gen_range(0..=nn.weight_count())).collect(),
+            _ => Vec::new(),
         };
+        cuts.sort_unstable();
+
+        let mut position: usize = 0;
+        for layer in 0..nn.layers.len() {
+            for neuron in 0..nn.layers[layer].w.len() {
+                // (parent, activation) of every parent sharing this neuron
+                let activations: Vec<(usize, activators::Type)> = parents.iter().enumerate()
+                    .filter_map(|(p, parent)| parent.aligned_activation(layer, neuron, depth).map(|a| (p, a)))
+                    .collect();
+                nn.layers[layer].a[neuron] = match operator {
+                    CrossoverOperator::Uniform => activations[rng.gen_range(0..activations.len())].1,
+                    CrossoverOperator::Diagonal => {
+                        let p = cuts.partition_point(|cut| *cut <= position);
+                        activations.iter().find(|(a, _)| *a == p).map(|(_, a)| *a).unwrap_or(nn.layers[layer].a[neuron])
+                    },
+                    // Activations can't be averaged, centroid draws them by fitness too
+                    _ => roulette(&activations, &parent_weights, &mut rng),
+                };
+
+                let previous = nn.previous_width(layer);
+                for weight in 0..nn.layers[layer].w[neuron].len() {
+                    // (parent, gene) of every parent sharing this weight
+                    let genes: Vec<(usize, f64)> = parents.iter().enumerate()
+                        .filter_map(|(p, parent)| parent.aligned_gene(layer, neuron, weight, previous, depth).map(|g| (p, g)))
+                        .collect();
+                    let total = genes.iter().fold(0.0, |sum, (p, _)| sum + parent_weights[*p]);
 
-        for i in 0..p1.layers.len() {
-            nn.layers.push(Layer::new_from_parents(
-                &p1.layers[i], 
-                &p2.layers[i], 
-                p1_fitness, p2_fitness
-            ))
+                    nn.layers[layer].w[neuron][weight] = match operator {
+                        CrossoverOperator::FitnessWeighted => roulette(&genes, &parent_weights, &mut rng),
+                        CrossoverOperator::Uniform => genes[rng.gen_range(0..genes.len())].1,
+                        CrossoverOperator::Centroid => {
+                            if total > 0.0 {
+                                genes.iter().fold(0.0, |sum, (p, g)| sum + parent_weights[*p] * g) / total
+                            } else {
+                                genes.iter().fold(0.0, |sum, (_, g)| sum + g) / genes.len() as f64
+                            }
+                        },
+                        CrossoverOperator::Diagonal => {
+                            let p = cuts.partition_point(|cut| *cut <= position);
+                            genes.iter().find(|(g, _)| *g == p).map(|(_, g)| *g).unwrap_or(nn.layers[layer].w[neuron][weight])
+                        },
+                    };
+                    position += 1;
+                }
+            }
         }
 
         nn
     }
 
+    /// Layer of this net matching the given layer of a net with `depth` layers
+    fn aligned_layer(&self, layer: usize, depth: usize) -> Option<usize> {
+        if layer == depth - 1 {
+            Some(self.layers.len() - 1)
+        } else if layer < self.layers.len() - 1 {
+            Some(layer)
+        } else {
+            None
+        }
+    }
+
+    /// Weight of this net matching the given weight of a net with `depth` layers,
+    /// whose layer reads `previous` outputs of its predecessor. Bias and predecessor
+    /// weights align with each other and input skip weights with each other
+    fn aligned_gene(&self, layer: usize, neuron: usize, weight: usize, previous: usize, depth: usize) -> Option<f64> {
+        let l = self.aligned_layer(layer, depth)?;
+        let own_previous = self.previous_width(l);
+        let position = if weight <= previous {
+            Some(weight).filter(|w| *w <= own_previous)
+        } else {
+            Some(own_previous + weight - previous).filter(|_| weight - previous <= self.skip_inputs(l))
+        };
+        position.and_then(|w| self.layers[l].w.get(neuron)?.get(w)).copied()
+    }
+
+    /// Outputs of the predecessor read by the given layer, the inputs for the first
+    fn previous_width(&self, layer: usize) -> usize {
+        self.layers[layer].w[0].len() - 1 - self.skip_inputs(layer)
+    }
+
+    /// Activation gene of this net matching the given neuron of a net with `depth` layers
+    fn aligned_activation(&self, layer: usize, neuron: usize, depth: usize) -> Option<activators::Type> {
+        self.aligned_layer(layer, depth).and_then(|l| self.layers[l].a.get(neuron)).copied()
+    }
+
+    /// Weights of both nets over the union of their shapes, missing weights count as 0.0
+    fn aligned_weights(&self, other: &EvoNet) -> (Vec<f64>, Vec<f64>) {
+        if self.get_architecture() == other.get_architecture() {
+            return (self.get_weights(), other.get_weights());
+        }
+
+        let (mut own, mut others) = (Vec::new(), Vec::new());
+        for (a, b, flip) in [(self, other, false), (other, self, true)] {
+            let depth = a.layers.len();
+            for layer in 0..depth {
+                let previous = a.previous_width(layer);
+                for neuron in 0..a.layers[layer].w.len() {
+                    for weight in 0..a.layers[layer].w[neuron].len() {
+                        let gene = b.aligned_gene(layer, neuron, weight, previous, depth);
+                        // Shared weights are only collected from self
+                        if flip && gene.is_some() {
+                            continue;
+                        }
+                        let pair = (a.layers[layer].w[neuron][weight], gene.unwrap_or(0.0));
+                        let (x, y) = if flip { (pair.1, pair.0) } else { pair };
+                        own.push(x);
+                        others.push(y);
+                    }
+                }
+            }
+        }
+        (own, others)
+    }
+
+    /// Neuron count of each layer, starting with the inputs
+    pub fn get_architecture(&self) -> Vec<usize> {
+        let mut architecture = vec![self.layers[0].w[0].len() - 1];
+        self.layers.iter().for_each(|l| architecture.push(l.v.len()));
+        architecture
+    }
+
+    /// Activation new hidden neurons start with
+    pub fn get_activation_type(&self) -> activators::Type {
+        self.act_type
+    }
+
+    /// Activation genes of the neurons of each hidden layer
+    pub fn get_neuron_activations(&self) -> Vec<Vec<activators::Type>> {
+        self.layers[..self.layers.len() - 1].iter().map(|l| l.a.clone()).collect()
+    }
+
+    /// Sets the activation of a hidden neuron. Returns false for the output
+    /// layer or a missing neuron
+    pub fn set_neuron_activation(&mut self, layer: usize, neuron: usize, act: activators::Type) -> bool {
+        if layer + 1 >= self.layers.len() || neuron >= self.layers[layer].a.len() {
+            return false;
+        }
+        self.layers[layer].a[neuron] = act;
+        true
+    }
+
+    /// Outputs of each layer from the last `calc`, starting with the first hidden layer
+    pub(crate) fn layer_outputs(&self) -> Vec<&[f64]> {
+        self.layers.iter().map(|l| l.y.as_slice()).collect()
+    }
+
+    /// Amount of weights, including biases, in the net
+    pub fn weight_count(&self) -> usize {
+        self.layers.iter().fold(0, |sum, l| sum + l.w.iter().fold(0, |sum, n| sum + n.len()))
+    }
+
+    /// Flattens the weights layer by layer, neuron by neuron
+    pub fn get_weights(&self) -> Vec<f64> {
+        let mut weights = Vec::with_capacity(self.weight_count());
+        self.layers.iter().for_each(|l| l.w.iter().for_each(|n| weights.extend_from_slice(n)));
+        weights
+    }
+
+    /// Overwrites the weights from a flat vector in the order of `get_weights`
+    pub fn set_weights(&mut self, weights: &[f64]) {
+        let mut position: usize = 0;
+        self.layers.iter_mut().for_each(|l| l.w.iter_mut().for_each(|n| {
+            let len = n.len();
+            n.copy_from_slice(&weights[position..position + len]);
+            position += len;
+        }));
+    }
+
+    /// Redraws the weights layer by layer, `schemes[i]` for the i-th layer.
+    /// Layers past the end of `schemes` use `WeightInit::default()`
+    pub fn initialize_weights<R: Rng>(&mut self, schemes: &[WeightInit], rng: &mut R) {
+        self.layers.iter_mut().enumerate().for_each(|(i, l)| {
+            schemes.get(i).copied().unwrap_or_default().initialize(&mut l.w, rng);
+        });
+    }
+
+    /// Euclidean distance between the weights of two nets
+    pub fn distance(&self, other: &EvoNet) -> f64 {
+        self.distance_with(other, DistanceMetric::Euclidean)
+    }
+
+    /// Distance between the weights of two nets. Nets of different shapes are
+    /// aligned like in crossover, weights only one of them has count against 0.0
+    pub fn distance_with(&self, other: &EvoNet, metric: DistanceMetric) -> f64 {
+        let (own, others) = self.aligned_weights(other);
+        metric.measure(&own, &others)
+    }
+
     pub fn set_fitness(&mut self, ft: f64) {
         self.fitness = ft;
     }
 
-    fn forward(&mut self, x: &Vec<f64>) {
+    fn forward(&mut self, x: &[f64]) {
         let mut sum: f64;
 
         for j in 0..self.layers.len() {
             if j == 0 {
                 for i in 0..self.layers[j].v.len(){
                     sum = 0.0;
-                    for k in 0..x.len(){
-                        sum += self.layers[j].w[i][k] * x[k];
+                    for (k, x_k) in x.iter().enumerate() {
+                        sum += self.layers[j].w[i][k] * x_k;
                     }
+                    let act = self.activation(j, i);
                     self.layers[j].v[i] = sum;
-                    self.layers[j].y[i] = (self.act.func)(sum);
-                }
-            } else if j == self.layers.len() - 1 {
-                for i in 0..self.layers[j].v.len(){
-                    sum = self.layers[j].w[i][0];
-                    for k in 0..self.layers[j - 1].y.len(){
-                        sum += self.layers[j].w[i][k + 1] * self.layers[j - 1].y[k];
-                    }
-                    self.layers[j].v[i] = sum;
-                    self.layers[j].y[i] = sum;
+                    self.layers[j].y[i] = act(sum);
                 }
             } else {
+                let previous = self.layers[j - 1].y.len();
+                let skips = self.skip_inputs(j);
+
                 for i in 0..self.layers[j].v.len(){
                     sum = self.layers[j].w[i][0];
-                    for k in 0..self.layers[j - 1].y.len(){
+                    for k in 0..previous {
                         sum += self.layers[j].w[i][k + 1] * self.layers[j - 1].y[k];
                     }
+                    for k in 0..skips {
+                        sum += self.layers[j].w[i][previous + k + 1] * x[k + 1];
+                    }
+                    let act = if j == self.layers.len() - 1 { activators::identity } else { self.activation(j, i) };
                     self.layers[j].v[i] = sum;
-                    self.layers[j].y[i] = (self.act.func)(sum);
+                    self.layers[j].y[i] = act(sum);
+                    if self.layers[j].r && i < previous {
+                        self.layers[j].y[i] += self.layers[j - 1].y[i];
+                    }
                 }
             }
         }
     }
 
+    /// Raw inputs fed to the given layer besides the previous layer's outputs
+    fn skip_inputs(&self, layer: usize) -> usize {
+        if self.config.input_skips && layer > 0 {
+            self.layers[0].w[0].len() - 1
+        } else {
+            0
+        }
+    }
+
+    /// Function of a hidden neuron's activation gene, `Custom` falls back to the container
+    fn activation(&self, layer: usize, neuron: usize) -> fn(f64) -> f64 {
+        self.layers[layer].a[neuron].function().unwrap_or(self.act.func)
+    }
+
     #[allow(non_snake_case)]
     pub fn calc(&mut self, X: &[f64]) -> &[f64]{
         let mut x = X.to_vec();
@@ -159,27 +391,179 @@ impl EvoNet {
     }
 
     pub fn mutate(&mut self, frequency: f64) {
+        self.mutate_with(frequency, MutationOperator::default());
+    }
+
+    /// Perturbs each weight with probability `frequency` using the given operator,
+    /// then redraws activations and applies the structural mutations at the rates of the config
+    pub fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
         let mut rng = thread_rng();
         for layer in 0..self.layers.len() {
             for neuron in 0..self.layers[layer].v.len() {
                 for weight in 0..self.layers[layer].w[neuron].len() {
                     if rng.gen_range(0.0..=1.0) <= frequency {
-                        let amount = 0.1 * rng.sample::<f64, _>(StandardNormal);
-                        self.layers[layer].w[neuron][weight] = (self.layers[layer].w[neuron][weight] + amount).clamp(0.0, 1.0);
+                        let w = &mut self.layers[layer].w[neuron][weight];
+                        *w = operator.perturb(*w, &mut rng);
                     }
                 }
             }
         }
+
+        for layer in 0..self.layers.len() - 1 {
+            for act in self.layers[layer].a.iter_mut() {
+                if rng.gen_range(0.0..1.0) < self.config.activation_mutation_rate {
+                    *act = activators::EVOLVABLE[rng.gen_range(0..activators::EVOLVABLE.len())];
+                }
+            }
+        }
+
+        self.mutate_structure();
+    }
+
+    fn mutate_structure(&mut self) {
+        let mut rng = thread_rng();
+        let hidden = self.layers.len() - 1;
+
+        if hidden > 0 && rng.gen_range(0.0..1.0) < self.config.add_neuron_rate {
+            self.add_neuron(rng.gen_range(0..hidden));
+        }
+        if rng.gen_range(0.0..1.0) < self.config.remove_neuron_rate {
+            let candidates: Vec<usize> = (0..hidden).filter(|l| self.layers[*l].v.len() > 1).collect();
+            if !candidates.is_empty() {
+                let layer = candidates[rng.gen_range(0..candidates.len())];
+                let width = self.layers[layer].v.len();
+                let neuron = if self.is_residual_linked(layer) { width - 1 } else { rng.gen_range(0..width) };
+                self.remove_neuron(layer, neuron);
+            }
+        }
+        if rng.gen_range(0.0..1.0) < self.config.add_layer_rate {
+            self.insert_layer(rng.gen_range(0..self.layers.len()));
+        }
+        let hidden = self.layers.len() - 1;
+        if hidden > 0 && rng.gen_range(0.0..1.0) < self.config.remove_layer_rate {
+            self.remove_layer(rng.gen_range(0..hidden));
+        }
+    }
+
+    /// Adds a neuron with random incoming and zero outgoing weights to a hidden
+    /// layer, leaving the output unchanged. Returns false for the output layer
+    pub fn add_neuron(&mut self, layer: usize) -> bool {
+        if layer + 1 >= self.layers.len() {
+            return false;
+        }
+        let inputs = self.layers[layer].w[0].len() - 1;
+        let neuron = Layer::new(1, inputs, self.act_type);
+        self.layers[layer].v.push(0.0);
+        self.layers[layer].y.push(0.0);
+        self.layers[layer].w.extend(neuron.w);
+        self.layers[layer].a.extend(neuron.a);
+        let position = self.layers[layer].v.len();
+        self.layers[layer + 1].w.iter_mut().for_each(|n| n.insert(position, 0.0));
+        true
+    }
+
+    /// Removes a neuron and its outgoing weights from a hidden layer. In a layer
+    /// linked to its neighbours by a residual add only the last neuron can be
+    /// removed, so the others keep their residual pairs.
+    /// Returns false for the output layer, a layer's only neuron or a refused neuron
+    pub fn remove_neuron(&mut self, layer: usize, neuron: usize) -> bool {
+        if layer + 1 >= self.layers.len() || self.layers[layer].v.len() <= 1 || neuron >= self.layers[layer].v.len() {
+            return false;
+        }
+        if self.is_residual_linked(layer) && neuron + 1 != self.layers[layer].v.len() {
+            return false;
+        }
+        self.layers[layer].v.remove(neuron);
+        self.layers[layer].y.remove(neuron);
+        self.layers[layer].w.remove(neuron);
+        self.layers[layer].a.remove(neuron);
+        self.layers[layer + 1].w.iter_mut().for_each(|n| { n.remove(neuron + 1); });
+        true
+    }
+
+    /// Inserts an identity initialised hidden layer in front of the layer at
+    /// `position`, as wide as its input. The output only changes through the
+    /// activation of the new layer, which never gets a residual add. A net without
+    /// hidden layers already activates its only layer, it turns hidden instead and
+    /// a linear identity output layer follows, leaving the output unchanged.
+    /// Returns false past the output layer
+    pub fn insert_layer(&mut self, position: usize) -> bool {
+        if position >= self.layers.len() {
+            return false;
+        }
+        if self.layers.len() == 1 {
+            let width = self.layers[0].v.len();
+            self.layers.push(Layer::identity(width, self.act_type));
+            let skips = self.skip_inputs(1);
+            self.layers[1].w.iter_mut().for_each(|n| n.resize(n.len() + skips, 0.0));
+            return true;
+        }
+        let skips = self.skip_inputs(position);
+        let width = self.layers[position].w[0].len() - 1 - skips;
+        self.layers.insert(position, Layer::identity(width, self.act_type));
+
+        // Input skips start at 0.0 in the new layer, or in the old first layer it pushed back
+        let grown = position.max(1);
+        let skips = self.skip_inputs(grown);
+        self.layers[grown].w.iter_mut().for_each(|n| n.resize(n.len() + skips, 0.0));
+        true
+    }
+
+    /// Removes a hidden layer, the following layer takes over its inputs.
+    /// Weights of inputs the following layer lacks start at 0.0, with input
+    /// skips the new first layer keeps its skip weights as input weights.
+    /// Returns false for the output layer
+    pub fn remove_layer(&mut self, layer: usize) -> bool {
+        if layer + 1 >= self.layers.len() {
+            return false;
+        }
+        let inputs = self.layers[layer].w[0].len() - self.skip_inputs(layer);
+        let width = self.layers[layer].v.len();
+        self.layers.remove(layer);
+        self.layers[layer].w.iter_mut().for_each(|n| {
+            let skips = n.split_off(width + 1);
+            if layer == 0 && !skips.is_empty() {
+                n.truncate(1);
+            } else {
+                n.resize(inputs, 0.0);
+            }
+            n.extend(skips);
+        });
+        // The first layer reads the raw inputs, it has nothing to add
+        self.layers[0].r &= layer > 0;
+        true
+    }
+
+    /// True if the layer adds its predecessor's outputs or its successor adds its own
+    fn is_residual_linked(&self, layer: usize) -> bool {
+        self.layers[layer].r || self.layers.get(layer + 1).is_some_and(|l| l.r)
+    }
+
+}
+
+/// Draws the gene of a parent with probability proportional to its share
+fn roulette<T: Copy, R: Rng>(genes: &[(usize, T)], shares: &[f64], rng: &mut R) -> T {
+    let total = genes.iter().fold(0.0, |sum, (p, _)| sum + shares[*p]);
+    let mut ball = rng.gen_range(0.0..1.0) * total;
+    for (p, g) in genes.iter() {
+        ball -= shares[*p];
+        if ball < 0.0 {
+            return *g;
+        }
     }
-    
+    genes[genes.len() - 1].1
 }
 
 impl Display for EvoNet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut net_str = String::from("");
 
-        self.layers.iter().for_each(|l| {
-            net_str.push_str("layer\n");
+        self.layers.iter().enumerate().for_each(|(i, l)| {
+            if i + 1 < self.layers.len() {
+                net_str.push_str(&format!("layer {:?}\n", l.a));
+            } else {
+                net_str.push_str("layer\n");
+            }
             net_str.push_str(&l.to_string());
         });
 
@@ -191,4 +575,168 @@ impl HasFitness for EvoNet {
     fn get_fitness(&self) -> f64 {
         self.fitness
     }
-}
\ No newline at end of file
+}
+
+impl Network for EvoNet {
+    fn calc(&mut self, x: &[f64]) -> &[f64] {
+        EvoNet::calc(self, x)
+    }
+}
+
+impl Genome for EvoNet {
+    type Config = EvoNetConfig;
+
+    fn spawn(architecture: &[usize], config: &EvoNetConfig) -> Self {
+        EvoNet::with_config(architecture, config)
+    }
+
+    fn from_many_parents(parents: &[&Self], fitnesses: &[f64], operator: CrossoverOperator) -> Self {
+        EvoNet::from_many_parents(parents, fitnesses, operator)
+    }
+
+    fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        EvoNet::mutate_with(self, frequency, operator)
+    }
+
+    fn distance_with(&self, other: &Self, metric: DistanceMetric) -> f64 {
+        EvoNet::distance_with(self, other, metric)
+    }
+
+    fn set_fitness(&mut self, ft: f64) {
+        EvoNet::set_fitness(self, ft)
+    }
+
+    fn get_architecture(&self) -> Vec<usize> {
+        EvoNet::get_architecture(self)
+    }
+
+    /// Crossover aligns the hidden layers, only the inputs and outputs must match
+    fn crossover_compatible(&self, other: &Self) -> bool {
+        self.layers[0].w[0].len() == other.layers[0].w[0].len()
+            && self.layers[self.layers.len() - 1].v.len() == other.layers[other.layers.len() - 1].v.len()
+    }
+
+    fn weight_layer_count(architecture: &[usize], _config: &EvoNetConfig) -> Option<usize> {
+        Some(architecture.len().saturating_sub(1))
+    }
+
+    fn initialize_weights(&mut self, schemes: &[WeightInit], rng: &mut StdRng) {
+        EvoNet::initialize_weights(self, schemes, rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EvoNet, EvoNetConfig};
+    use crate::{activators, evotrainer::crossover::CrossoverOperator};
+
+    const OPERATORS: [CrossoverOperator; 4] = [CrossoverOperator::FitnessWeighted, CrossoverOperator::Uniform, CrossoverOperator::Centroid, CrossoverOperator::Diagonal];
+    const X: [f64; 2] = [0.3, -0.7];
+
+    /// Plain, input skip, residual and combined topologies
+    fn configs() -> [EvoNetConfig; 4] {
+        let skips = EvoNetConfig { input_skips: true, ..Default::default() };
+        let residual = EvoNetConfig { residual: true, ..Default::default() };
+        [EvoNetConfig::default(), skips, residual, EvoNetConfig { input_skips: true, residual: true, ..Default::default() }]
+    }
+
+    fn assert_close(a: &[f64], b: &[f64]) {
+        assert_eq!(a.len(), b.len());
+        a.iter().zip(b.iter()).for_each(|(x, y)| assert!((x - y).abs() < 1e-9, "{} != {}", x, y));
+    }
+
+    #[test]
+    fn crossover_of_different_shapes_keeps_the_fittest_shape() {
+        let pairs: [(&[usize], &[usize]); 3] = [(&[2, 3, 1], &[2, 5, 2, 1]), (&[2, 4, 4, 1], &[2, 4, 1]), (&[2, 1], &[2, 3, 3, 1])];
+        for config in configs() {
+            for (a, b) in pairs {
+                let (a, b) = (EvoNet::with_config(a, &config), EvoNet::with_config(b, &config));
+                for operator in OPERATORS {
+                    for (fitnesses, fittest) in [([1.0, 0.0], &a), ([0.0, 1.0], &b)] {
+                        let mut child = EvoNet::from_many_parents(&[&a, &b], &fitnesses, operator);
+                        assert_eq!(child.get_architecture(), fittest.get_architecture());
+                        let output = child.calc(&X);
+                        assert_eq!(output.len(), 1);
+                        assert!(output.iter().all(|y| y.is_finite()));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn crossover_aligns_the_weights_of_a_grown_parent() {
+        for config in configs() {
+            let a = EvoNet::with_config(&[2, 3, 3, 1], &config);
+            let mut b = a.clone();
+            assert!(b.add_neuron(0));
+            assert!(b.add_neuron(1));
+            // Every weight of `a` is shared by `b`, whatever parent it comes from
+            for operator in OPERATORS {
+                let child = EvoNet::from_many_parents(&[&a, &b], &[1.0, 0.5], operator);
+                assert_close(&child.get_weights(), &a.get_weights());
+            }
+        }
+    }
+
+    #[test]
+    fn add_neuron_and_insert_layer_keep_the_output() {
+        for config in configs() {
+            for architecture in [&[2, 1][..], &[2, 3, 1], &[2, 3, 3, 1]] {
+                let mut net = EvoNet::with_config(architecture, &config);
+                let expected = net.calc(&X).to_vec();
+                let depth = architecture.len() - 1;
+
+                let mut grown = net.clone();
+                for layer in 0..depth - 1 {
+                    assert!(grown.add_neuron(layer));
+                }
+                assert!(!grown.add_neuron(depth - 1));
+                assert_close(grown.calc(&X), &expected);
+
+                for position in 0..depth {
+                    let mut grown = net.clone();
+                    assert!(grown.insert_layer(position));
+                    // A hidden identity layer only passes its inputs on with a linear activation
+                    if depth > 1 {
+                        let width = grown.get_architecture()[position + 1];
+                        (0..width).for_each(|n| { grown.set_neuron_activation(position, n, activators::Type::Identity); });
+                    }
+                    assert_close(grown.calc(&X), &expected);
+                }
+                assert!(!net.insert_layer(depth));
+            }
+        }
+    }
+
+    #[test]
+    fn remove_neuron_and_remove_layer_keep_the_inputs_and_outputs() {
+        for config in configs() {
+            let mut net = EvoNet::with_config(&[2, 4, 4, 3, 2], &config);
+            while net.get_architecture().len() > 2 {
+                let architecture = net.get_architecture();
+                for layer in 0..architecture.len() - 2 {
+                    while net.remove_neuron(layer, net.get_architecture()[layer + 1] - 1) {
+                        let architecture = net.get_architecture();
+                        assert_eq!((architecture[0], architecture[architecture.len() - 1]), (2, 2));
+                        assert!(net.calc(&X).iter().all(|y| y.is_finite()));
+                    }
+                }
+                assert!(!net.remove_layer(architecture.len() - 2));
+                assert!(net.remove_layer(0));
+                let architecture = net.get_architecture();
+                assert_eq!((architecture[0], architecture[architecture.len() - 1]), (2, 2));
+                assert_eq!(net.calc(&X).len(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn remove_neuron_keeps_residual_pairs() {
+        let config = EvoNetConfig { residual: true, ..Default::default() };
+        let mut net = EvoNet::with_config(&[2, 4, 4, 1], &config);
+        assert!(!net.remove_neuron(0, 0));
+        assert!(net.remove_neuron(0, 3));
+        assert_eq!(net.get_architecture(), vec![2, 3, 4, 1]);
+    }
+}
@@ -0,0 +1,125 @@
+use rand::Rng;
+
+/// How the trainer re-weights its parent selection strategies,
+/// crossover operators and mutation operators between generations.
+/// The reward of an operator is the share of its offspring that
+/// beat their best parent during the last generation
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OperatorAdaptation {
+    /// Operators keep the weights they were configured with
+    #[default]
+    Static,
+    /// (min_probability, adaptation_rate)
+    /// Probabilities proportional to the estimated operator quality,
+    /// never dropping below min_probability
+    ProbabilityMatching(f64, f64),
+    /// (min_probability, adaptation_rate, learning_rate)
+    /// Pursues the operator with the best estimated quality, moving its
+    /// probability towards the maximum and all others towards min_probability
+    AdaptivePursuit(f64, f64, f64)
+}
+
+/// Selection probabilities and success bookkeeping of a set of operators
+#[derive(Clone, Debug)]
+pub struct OperatorPool {
+    probabilities: Vec<f64>,
+    quality: Vec<f64>,
+    successes: Vec<usize>,
+    trials: Vec<usize>
+}
+
+impl OperatorPool {
+    /// Pool starting with probabilities proportional to the given weights
+    pub fn new(weights: &[usize]) -> Self {
+        let weight_sum = weights.iter().sum::<usize>();
+        let probabilities: Vec<f64> = weights.iter().map(|w| {
+            if weight_sum == 0 {
+                1.0 / weights.len() as f64
+            } else {
+                *w as f64 / weight_sum as f64
+            }
+        }).collect();
+
+        Self {
+            quality: probabilities.clone(),
+            probabilities,
+            successes: vec![0; weights.len()],
+            trials: vec![0; weights.len()],
+        }
+    }
+
+    pub fn get_probabilities(&self) -> &[f64] {
+        &self.probabilities
+    }
+
+    /// Picks an operator index with the current probabilities
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> usize {
+        let mut ball = rng.gen_range(0.0..1.0);
+        for (i, p) in self.probabilities.iter().enumerate() {
+            ball -= p;
+            if ball < 0.0 {
+                return i;
+            }
+        }
+        self.probabilities.len() - 1
+    }
+
+    /// Records whether an offspring created by operator `index` beat its parents
+    pub fn record(&mut self, index: usize, success: bool) {
+        self.trials[index] += 1;
+        if success {
+            self.successes[index] += 1;
+        }
+    }
+
+    /// Updates the probabilities from the outcomes recorded since the last
+    /// update and clears them. Operators without trials keep their quality
+    pub fn update(&mut self, adaptation: OperatorAdaptation) {
+        let k = self.probabilities.len() as f64;
+        let (min_probability, adaptation_rate) = match adaptation {
+            OperatorAdaptation::Static => {
+                self.clear();
+                return;
+            },
+            OperatorAdaptation::ProbabilityMatching(min_probability, adaptation_rate) => (min_probability, adaptation_rate),
+            OperatorAdaptation::AdaptivePursuit(min_probability, adaptation_rate, _) => (min_probability, adaptation_rate),
+        };
+
+        for i in 0..self.quality.len() {
+            if self.trials[i] > 0 {
+                let reward = self.successes[i] as f64 / self.trials[i] as f64;
+                self.quality[i] += adaptation_rate * (reward - self.quality[i]);
+            }
+        }
+
+        match adaptation {
+            OperatorAdaptation::ProbabilityMatching(..) => {
+                let quality_sum = self.quality.iter().sum::<f64>();
+                for i in 0..self.probabilities.len() {
+                    self.probabilities[i] = if quality_sum > 0.0 {
+                        min_probability + (1.0 - k * min_probability) * self.quality[i] / quality_sum
+                    } else {
+                        1.0 / k
+                    };
+                }
+            },
+            OperatorAdaptation::AdaptivePursuit(_, _, learning_rate) => {
+                let max_probability = 1.0 - (k - 1.0) * min_probability;
+                let best = self.quality.iter().enumerate()
+                    .fold(0, |best, (i, q)| if *q > self.quality[best] { i } else { best });
+                for i in 0..self.probabilities.len() {
+                    let target = if i == best { max_probability } else { min_probability };
+                    self.probabilities[i] += learning_rate * (target - self.probabilities[i]);
+                }
+            },
+            OperatorAdaptation::Static => {}
+        }
+
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.successes.iter_mut().for_each(|s| *s = 0);
+        self.trials.iter_mut().for_each(|t| *t = 0);
+    }
+}
@@ -0,0 +1,475 @@
+use rand::{rngs::ThreadRng, Rng};
+use rand_distr::StandardNormal;
+use crate::evonet::EvoNet;
+use super::{
+    evotrainer::HasFitness,
+    objective::Objective,
+    statistics::GenerationStats,
+    trainer_builder::TrainerBuildError
+};
+
+/// State of the covariance matrix adaptation specific to one generation
+#[derive(Clone, Debug, Default)]
+pub struct CmaEsStats {
+    pub generation: usize,
+    /// Global step size sigma
+    pub step_size: f64,
+    /// Ratio of the largest to the smallest eigenvalue of the covariance matrix
+    pub condition_number: f64,
+    /// Offspring sampled this generation, grows with every IPOP restart
+    pub population_size: usize,
+    /// Restarts performed so far
+    pub restarts: usize,
+}
+
+/// Covariance matrix adaptation evolution strategy over the flattened
+/// weights of a fixed architecture, restarting with an increasing
+/// population size (IPOP) once the search has converged
+pub struct CmaEsTrainer {
+    template: EvoNet,
+    fitness_fn: fn(&mut EvoNet) -> f64,
+    objective: Objective,
+    dimension: usize,
+    initial_step_size: f64,
+    population_size: usize,
+    max_restarts: usize,
+    population_increase: f64,
+    tolerance_x: f64,
+    tolerance_fun: f64,
+
+    mean: Vec<f64>,
+    step_size: f64,
+    covariance: Vec<Vec<f64>>,
+    basis: Vec<Vec<f64>>,
+    scales: Vec<f64>,
+    path_c: Vec<f64>,
+    path_s: Vec<f64>,
+    eigen_generation: usize,
+    run_generation: usize,
+    recent_best: Vec<f64>,
+
+    best: Option<EvoNet>,
+    restarts: usize,
+    generation: usize,
+    statistics: Vec<GenerationStats>,
+    cma_statistics: Vec<CmaEsStats>
+}
+
+impl CmaEsTrainer {
+    pub fn get_statistics(&self) -> &[GenerationStats] {
+        &self.statistics
+    }
+
+    pub fn get_cma_statistics(&self) -> &[CmaEsStats] {
+        &self.cma_statistics
+    }
+
+    pub fn get_step_size(&self) -> f64 {
+        self.step_size
+    }
+
+    pub fn get_condition_number(&self) -> f64 {
+        let max = self.scales.iter().fold(0.0, |max: f64, d| max.max(*d));
+        let min = self.scales.iter().fold(f64::INFINITY, |min, d| min.min(*d));
+        (max / min).powi(2)
+    }
+
+    /// Best net found over all generations and restarts
+    pub fn extract_best(&self) -> EvoNet {
+        match self.best.as_ref() {
+            Some(net) => net.clone(),
+            None => {
+                let mut net = self.template.clone();
+                net.set_weights(&self.mean);
+                net
+            }
+        }
+    }
+
+    pub fn train(&mut self, generations: usize) {
+        let mut rng = rand::thread_rng();
+        (0..generations).for_each(|_| {
+            self.step(&mut rng);
+            if self.has_converged() && self.restarts < self.max_restarts {
+                self.restart(&mut rng);
+            }
+        });
+    }
+
+    fn step(&mut self, rng: &mut ThreadRng) {
+        let n = self.dimension;
+        let lambda = self.population_size;
+        let mu = lambda / 2;
+        let weights = Self::recombination_weights(mu);
+        let mueff = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+        let nf = n as f64;
+
+        let cc = (4.0 + mueff / nf) / (nf + 4.0 + 2.0 * mueff / nf);
+        let cs = (mueff + 2.0) / (nf + mueff + 5.0);
+        let c1 = 2.0 / ((nf + 1.3).powi(2) + mueff);
+        let cmu = (1.0 - c1).min(2.0 * (mueff - 2.0 + 1.0 / mueff) / ((nf + 2.0).powi(2) + mueff));
+        let damps = 1.0 + 2.0 * (((mueff - 1.0) / (nf + 1.0)).sqrt() - 1.0).max(0.0) + cs;
+        let chi_n = nf.sqrt() * (1.0 - 1.0 / (4.0 * nf) + 1.0 / (21.0 * nf * nf));
+
+        // Sample and evaluate the offspring, y = B * D * z
+        let mut offspring: Vec<(f64, Vec<f64>, EvoNet)> = (0..lambda).map(|_| {
+            let z: Vec<f64> = (0..n).map(|_| rng.sample::<f64, _>(StandardNormal)).collect();
+            let y: Vec<f64> = (0..n).map(|i| (0..n).fold(0.0, |sum, j| sum + self.basis[i][j] * self.scales[j] * z[j])).collect();
+            let x: Vec<f64> = (0..n).map(|i| self.mean[i] + self.step_size * y[i]).collect();
+
+            let mut net = self.template.clone();
+            net.set_weights(&x);
+            let fitness = (self.fitness_fn)(&mut net);
+            net.set_fitness(fitness);
+            (fitness, y, net)
+        }).collect();
+        offspring.sort_by(|a, b| self.objective.compare(b.0, a.0));
+
+        self.record_generation(&offspring);
+
+        // Recombine the mu best into the new mean
+        let mut y_w = vec![0.0; n];
+        for (k, w) in weights.iter().enumerate() {
+            y_w.iter_mut().zip(offspring[k].1.iter()).for_each(|(y, o)| *y += w * o);
+        }
+        self.mean.iter_mut().zip(y_w.iter()).for_each(|(m, y)| *m += self.step_size * y);
+
+        // C^-1/2 * y_w = B * D^-1 * B^T * y_w
+        let bt_y: Vec<f64> = (0..n).map(|j| (0..n).fold(0.0, |sum, i| sum + self.basis[i][j] * y_w[i])).collect();
+        let inv_sqrt_c_y: Vec<f64> = (0..n).map(|i| (0..n).fold(0.0, |sum, j| sum + self.basis[i][j] * bt_y[j] / self.scales[j])).collect();
+
+        let cs_norm = (cs * (2.0 - cs) * mueff).sqrt();
+        self.path_s.iter_mut().zip(inv_sqrt_c_y.iter()).for_each(|(p, y)| *p = (1.0 - cs) * *p + cs_norm * y);
+        let ps_norm = self.path_s.iter().map(|p| p * p).sum::<f64>().sqrt();
+        self.run_generation += 1;
+        let hsig = ps_norm / (1.0 - (1.0 - cs).powi(2 * self.run_generation as i32)).sqrt() / chi_n < 1.4 + 2.0 / (nf + 1.0);
+        let hsig = if hsig { 1.0 } else { 0.0 };
+
+        let cc_norm = (cc * (2.0 - cc) * mueff).sqrt();
+        self.path_c.iter_mut().zip(y_w.iter()).for_each(|(p, y)| *p = (1.0 - cc) * *p + hsig * cc_norm * y);
+
+        let c1a = c1 * (1.0 - (1.0 - hsig * hsig) * cc * (2.0 - cc));
+        for i in 0..n {
+            for j in 0..=i {
+                let rank_mu = weights.iter().enumerate().fold(0.0, |sum, (k, w)| sum + w * offspring[k].1[i] * offspring[k].1[j]);
+                let value = (1.0 - c1a - cmu) * self.covariance[i][j]
+                    + c1 * self.path_c[i] * self.path_c[j]
+                    + cmu * rank_mu;
+                self.covariance[i][j] = value;
+                self.covariance[j][i] = value;
+            }
+        }
+
+        self.step_size *= ((cs / damps) * (ps_norm / chi_n - 1.0)).min(1.0).exp();
+
+        // Decompose lazily, the covariance changes slowly
+        let gap = (lambda as f64 / (c1 + cmu) / nf / 10.0).max(1.0) as usize;
+        if self.run_generation - self.eigen_generation >= gap {
+            self.eigen_generation = self.run_generation;
+            self.decompose();
+        }
+
+        let best = offspring.swap_remove(0).2;
+        let improved = match self.best.as_ref() {
+            Some(current) => self.objective.is_better(best.get_fitness(), current.get_fitness()),
+            None => true,
+        };
+        self.recent_best.push(best.get_fitness());
+        if improved {
+            self.best = Some(best);
+        }
+
+        self.cma_statistics.push(CmaEsStats {
+            generation: self.generation,
+            step_size: self.step_size,
+            condition_number: self.get_condition_number(),
+            population_size: lambda,
+            restarts: self.restarts,
+        });
+        self.generation += 1;
+    }
+
+    fn record_generation(&mut self, offspring: &[(f64, Vec<f64>, EvoNet)]) {
+        let fitnesses: Vec<f64> = offspring.iter().map(|o| o.0).collect();
+        self.statistics.push(GenerationStats::from_fitnesses(self.generation, &fitnesses, self.objective));
+    }
+
+    /// Stops the current run when the steps, the fitness history
+    /// or the conditioning of the covariance give out
+    fn has_converged(&self) -> bool {
+        let max_scale = self.scales.iter().fold(0.0, |max: f64, d| max.max(*d));
+        if self.step_size * max_scale < self.tolerance_x {
+            return true;
+        }
+
+        if self.get_condition_number() > 1e14 || !self.step_size.is_finite() {
+            return true;
+        }
+
+        let history = 10 + (30 * self.dimension) / self.population_size;
+        if self.recent_best.len() >= history {
+            let recent = &self.recent_best[self.recent_best.len() - history..];
+            let max = recent.iter().fold(f64::NEG_INFINITY, |max, f| max.max(*f));
+            let min = recent.iter().fold(f64::INFINITY, |min, f| min.min(*f));
+            if max - min < self.tolerance_fun {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Restarts from a fresh random mean with a larger population
+    fn restart(&mut self, rng: &mut ThreadRng) {
+        self.restarts += 1;
+        self.population_size = (self.population_size as f64 * self.population_increase).ceil() as usize;
+        let mean: Vec<f64> = (0..self.dimension).map(|_| rng.gen_range(-1.0..=1.0)).collect();
+        self.reset(mean);
+    }
+
+    fn reset(&mut self, mean: Vec<f64>) {
+        let n = self.dimension;
+        self.mean = mean;
+        self.step_size = self.initial_step_size;
+        self.covariance = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+        self.basis = self.covariance.clone();
+        self.scales = vec![1.0; n];
+        self.path_c = vec![0.0; n];
+        self.path_s = vec![0.0; n];
+        self.eigen_generation = 0;
+        self.run_generation = 0;
+        self.recent_best.clear();
+    }
+
+    fn recombination_weights(mu: usize) -> Vec<f64> {
+        let raw: Vec<f64> = (1..=mu).map(|i| (mu as f64 + 0.5).ln() - (i as f64).ln()).collect();
+        let sum = raw.iter().sum::<f64>();
+        raw.iter().map(|w| w / sum).collect()
+    }
+
+    /// Eigen decomposition of the covariance with cyclic Jacobi rotations,
+    /// C = B * D^2 * B^T
+    fn decompose(&mut self) {
+        let n = self.dimension;
+        let mut a = self.covariance.clone();
+        let mut v: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+
+        for _ in 0..100 {
+            let off_diagonal = (0..n).fold(0.0, |sum, i| sum + (0..n).filter(|j| *j != i).fold(0.0, |s, j| s + a[i][j] * a[i][j]));
+            if off_diagonal < 1e-22 {
+                break;
+            }
+
+            for p in 0..n {
+                for q in p + 1..n {
+                    if a[p][q].abs() < 1e-300 {
+                        continue;
+                    }
+                    let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+
+                    for row in a.iter_mut() {
+                        let (akp, akq) = (row[p], row[q]);
+                        row[p] = c * akp - s * akq;
+                        row[q] = s * akp + c * akq;
+                    }
+                    let (row_p, row_q) = (a[p].clone(), a[q].clone());
+                    a[p].iter_mut().zip(row_q.iter()).zip(row_p.iter()).for_each(|((apk, aqk), old)| *apk = c * old - s * aqk);
+                    a[q].iter_mut().zip(row_p.iter()).zip(row_q.iter()).for_each(|((aqk, apk), old)| *aqk = s * apk + c * old);
+                    for row in v.iter_mut() {
+                        let (vkp, vkq) = (row[p], row[q]);
+                        row[p] = c * vkp - s * vkq;
+                        row[q] = s * vkp + c * vkq;
+                    }
+                }
+            }
+        }
+
+        self.basis = v;
+        self.scales = (0..n).map(|i| a[i][i].max(1e-20).sqrt()).collect();
+    }
+}
+
+pub struct CmaEsBuilder<'a> {
+    architecture: Option<&'a [usize]>,
+    fitness_function: Option<fn(&mut EvoNet) -> f64>,
+    objective: Objective,
+    initial_step_size: Option<f64>,
+    population_size: Option<usize>,
+    max_restarts: usize,
+    population_increase: f64,
+    tolerance_x: f64,
+    tolerance_fun: f64,
+}
+
+impl Default for CmaEsBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a> CmaEsBuilder<'a> {
+
+    pub fn new() -> Self {
+        Self {
+            architecture: None,
+            fitness_function: None,
+            objective: Objective::Maximize,
+            initial_step_size: None,
+            population_size: None,
+            max_restarts: 0,
+            population_increase: 2.0,
+            tolerance_x: 1e-11,
+            tolerance_fun: 1e-12,
+        }
+    }
+
+    pub fn build(&self) -> Result<CmaEsTrainer, TrainerBuildError> {
+        let arch = self.architecture.ok_or(TrainerBuildError::VariableNotSet(String::from("architecture not set")))?;
+        let ft_fn = self.fitness_function.ok_or(TrainerBuildError::VariableNotSet(String::from("fitness_function not set")))?;
+        let step_size = self.initial_step_size.unwrap_or(0.5);
+
+        if arch.contains(&0) {
+            return Err(TrainerBuildError::ValidationError(String::from("architecure cannot contain 0's")));
+        }
+
+        if step_size <= 0.0 {
+            return Err(TrainerBuildError::ValidationError(String::from("initial_step_size must be greater than 0.0")));
+        }
+
+        if self.population_increase < 1.0 {
+            return Err(TrainerBuildError::ValidationError(String::from("population_increase cannot be smaller than 1.0")));
+        }
+
+        let template = EvoNet::new(arch);
+        let dimension = template.weight_count();
+        let pop_size = self.population_size.unwrap_or(4 + (3.0 * (dimension as f64).ln()).floor() as usize);
+
+        if pop_size < 2 {
+            return Err(TrainerBuildError::ValidationError(String::from("population_size must be greater than 1")));
+        }
+
+        let mut trainer = CmaEsTrainer {
+            mean: template.get_weights(),
+            template,
+            fitness_fn: ft_fn,
+            objective: self.objective,
+            dimension,
+            initial_step_size: step_size,
+            population_size: pop_size,
+            max_restarts: self.max_restarts,
+            population_increase: self.population_increase,
+            tolerance_x: self.tolerance_x,
+            tolerance_fun: self.tolerance_fun,
+            step_size,
+            covariance: Vec::new(),
+            basis: Vec::new(),
+            scales: Vec::new(),
+            path_c: Vec::new(),
+            path_s: Vec::new(),
+            eigen_generation: 0,
+            run_generation: 0,
+            recent_best: Vec::new(),
+            best: None,
+            restarts: 0,
+            generation: 0,
+            statistics: Vec::new(),
+            cma_statistics: Vec::new(),
+        };
+        trainer.reset(trainer.mean.clone());
+
+        Ok(trainer)
+    }
+
+    pub fn set_architecture(&mut self, architecture: &'a[usize]) {
+        self.architecture = Some(architecture);
+    }
+
+    pub fn set_fitness_function(&mut self, fit_fn: fn(&mut EvoNet) -> f64) {
+        self.fitness_function = Some(fit_fn);
+    }
+
+    /// Defaults to `Objective::Maximize`
+    pub fn set_objective(&mut self, objective: Objective) {
+        self.objective = objective;
+    }
+
+    /// Starting sigma of every run. Defaults to 0.5
+    pub fn set_initial_step_size(&mut self, step_size: f64) {
+        self.initial_step_size = Some(step_size);
+    }
+
+    /// Offspring per generation of the first run.
+    /// Defaults to 4 + 3 * ln(weight count)
+    pub fn set_population_size(&mut self, size: usize) {
+        self.population_size = Some(size);
+    }
+
+    /// IPOP restarts allowed once a run converges. Defaults to 0
+    pub fn set_max_restarts(&mut self, restarts: usize) {
+        self.max_restarts = restarts;
+    }
+
+    /// Factor the population size grows by on every restart. Defaults to 2.0
+    pub fn set_population_increase(&mut self, factor: f64) {
+        self.population_increase = factor;
+    }
+
+    /// Run converges once sigma times the largest axis falls below this. Defaults to 1e-11
+    pub fn set_tolerance_x(&mut self, tolerance: f64) {
+        self.tolerance_x = tolerance;
+    }
+
+    /// Run converges once the recent best fitness values span less than this. Defaults to 1e-12
+    pub fn set_tolerance_fun(&mut self, tolerance: f64) {
+        self.tolerance_fun = tolerance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CmaEsBuilder, CmaEsTrainer};
+    use crate::evonet::EvoNet;
+
+    fn trainer() -> CmaEsTrainer {
+        let mut builder = CmaEsBuilder::new();
+        builder.set_architecture(&[2, 1]);
+        builder.set_fitness_function(|_: &mut EvoNet| 0.0);
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn decompose_finds_the_eigenpairs_of_a_symmetric_matrix() {
+        let mut cma = trainer();
+        // Eigenvalues 2 - sqrt(2), 2 and 2 + sqrt(2)
+        cma.covariance = vec![vec![2.0, 1.0, 0.0], vec![1.0, 2.0, 1.0], vec![0.0, 1.0, 2.0]];
+        cma.decompose();
+
+        let mut eigenvalues: Vec<f64> = cma.scales.iter().map(|d| d * d).collect();
+        eigenvalues.sort_by(f64::total_cmp);
+        let expected = [2.0 - 2f64.sqrt(), 2.0, 2.0 + 2f64.sqrt()];
+        eigenvalues.iter().zip(expected.iter()).for_each(|(value, e)| assert!((value - e).abs() < 1e-9));
+
+        // Columns of the basis are orthonormal and B D^2 B^T rebuilds the covariance
+        for i in 0..3 {
+            for j in 0..3 {
+                let dot = (0..3).fold(0.0, |sum, k| sum + cma.basis[k][i] * cma.basis[k][j]);
+                assert!((dot - if i == j { 1.0 } else { 0.0 }).abs() < 1e-9);
+
+                let rebuilt = (0..3).fold(0.0, |sum, k| sum + cma.basis[i][k] * cma.scales[k].powi(2) * cma.basis[j][k]);
+                assert!((rebuilt - cma.covariance[i][j]).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn decompose_keeps_a_diagonal_matrix() {
+        let mut cma = trainer();
+        cma.covariance = vec![vec![4.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 9.0]];
+        cma.decompose();
+
+        assert_eq!(cma.scales, vec![2.0, 1.0, 3.0]);
+        assert_eq!(cma.basis, vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 1.0]]);
+    }
+}
@@ -4,51 +4,128 @@ use crate::evotrainer::evotrainer::FitnessPair;
 
 #[derive(Clone)]
 pub enum Strategies {
-    /// (weight, rounds)
+    /// (weight, rounds, parents)
     Tournement(TournamentStrategy),
-    /// (weight, prime_parent_rate)
+    /// (weight, prime_parent_rate, parents)
     /// prime_parent_rate mut be between 0.0 and 1.0
     PrimeParent(PrimeParentStrategy),
-    /// (weight)
+    /// (weight, parents)
     Roulette(RouletteStrategy)
 }
 
 impl PartialEq for Strategies {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::Tournement(_), Self::Tournement(_)) => true,
-            (Self::PrimeParent(_), Self::PrimeParent(_)) => true,
-            (Self::Roulette(_), Self::Roulette(_)) => true,
-            _ => false,
+        matches!(
+            (self, other),
+            (Self::Tournement(_), Self::Tournement(_)) |
+            (Self::PrimeParent(_), Self::PrimeParent(_)) |
+            (Self::Roulette(_), Self::Roulette(_))
+        )
+    }
+}
+
+impl Strategies {
+    /// Amount of parents in each family the strategy creates
+    pub fn get_parent_count(&self) -> usize {
+        match self {
+            Strategies::Tournement(strat) => strat.get_parent_count(),
+            Strategies::PrimeParent(strat) => strat.get_parent_count(),
+            Strategies::Roulette(strat) => strat.get_parent_count(),
         }
     }
 }
 
-pub trait ParentSelectionStrategy {
+pub trait ParentSelectionStrategy: Send {
     /// Weight representing how much this strategy should be used
     /// in relation to other strategies being employed by the trainer
     fn get_weight(&self) -> usize;
 
+    /// Amount of parents in each family this strategy creates
+    fn get_parent_count(&self) -> usize;
+
     /// Takes the available parents and the population to be replaced
     /// by the offspring and returns the parents that will replace that 
-    /// member in the population
-    fn create_offspring(&self, parent_fitness_pairs: &Vec<FitnessPair>, crossover_pop: &[FitnessPair]) -> Vec<CrossoverFamily>;
+    /// member in the population.
+    /// Parents are sorted from worst to best and their fitness is oriented
+    /// so a bigger value is better regardless of the trainer's `Objective`
+    fn create_offspring(&self, parent_fitness_pairs: &[FitnessPair], crossover_pop: &[FitnessPair]) -> Vec<CrossoverFamily>;
 }
 
 pub struct CrossoverFamily {
     pub child_index: usize,
-    pub parent_a_index: usize,
-    pub parent_b_index: usize,
-    pub parent_a_fitness: f64,
-    pub parent_b_fitness: f64
+    pub parents: Vec<FitnessPair>
+}
+
+/// Operator recombining the weights of a family's parents into the child
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CrossoverOperator {
+    /// Each weight is inherited from a single parent,
+    /// picked with probability proportional to its fitness
+    #[default]
+    FitnessWeighted,
+    /// Each weight is inherited from a uniformly random parent
+    Uniform,
+    /// Each weight is the fitness weighted average of the parents' weights
+    Centroid,
+    /// The flattened weights are cut into one segment per parent
+    /// at random points, each segment inherited from the next parent
+    Diagonal
+}
+
+impl CrossoverOperator {
+    /// Recombines parents given as flat weight vectors of equal length
+    pub fn recombine(&self, parents: &[Vec<f64>], fitnesses: &[f64]) -> Vec<f64> {
+        let mut rng = rand::thread_rng();
+        let shares = parent_shares(fitnesses);
+        let length = parents[0].len();
+
+        let mut cuts: Vec<usize> = match self {
+            CrossoverOperator::Diagonal => (1..parents.len()).map(|_| rng.gen_range(0..=length)).collect(),
+            _ => Vec::new(),
+        };
+        cuts.sort_unstable();
+
+        (0..length).map(|i| match self {
+            CrossoverOperator::FitnessWeighted => {
+                let mut ball = rng.gen_range(0.0..1.0);
+                let mut p = parents.len() - 1;
+                for (j, share) in shares.iter().enumerate() {
+                    ball -= share;
+                    if ball < 0.0 {
+                        p = j;
+                        break;
+                    }
+                }
+                parents[p][i]
+            },
+            CrossoverOperator::Uniform => parents[rng.gen_range(0..parents.len())][i],
+            CrossoverOperator::Centroid => shares.iter().zip(parents.iter()).fold(0.0, |sum, (share, p)| sum + share * p[i]),
+            CrossoverOperator::Diagonal => parents[cuts.partition_point(|cut| *cut <= i)][i],
+        }).collect()
+    }
+}
+
+/// Share of each parent in a recombination, summing to 1.0. Shares are
+/// proportional to the fitness rank, 1 for the worst parent, so every parent
+/// contributes and the sign of the fitness (e.g. minimised objectives) doesn't matter.
+/// Tied parents share their average rank
+pub fn parent_shares(fitnesses: &[f64]) -> Vec<f64> {
+    let n = fitnesses.len() as f64;
+    let rank_sum = n * (n + 1.0) / 2.0;
+    fitnesses.iter().map(|ft| {
+        let below = fitnesses.iter().filter(|other| *other < ft).count() as f64;
+        let tied = fitnesses.iter().filter(|other| *other == ft).count() as f64;
+        (below + (tied + 1.0) / 2.0) / rank_sum
+    }).collect()
 }
 
 /// Randomly selects parents from the set amount of rounds
-/// Picking the best out of two tournements to be parents 
+/// Picking the best out of one tournement per parent
 #[derive(Clone)]
 pub struct TournamentStrategy {
     pub weight: usize,
-    pub rounds: usize
+    pub rounds: usize,
+    pub parents: usize
 }
 
 impl ParentSelectionStrategy for TournamentStrategy {
@@ -56,36 +133,29 @@ impl ParentSelectionStrategy for TournamentStrategy {
         self.weight
     }
 
-    fn create_offspring(&self, parent_fitness_pairs: &Vec<FitnessPair>, crossover_pop: &[FitnessPair]) -> Vec<CrossoverFamily> {
+    fn get_parent_count(&self) -> usize {
+        self.parents
+    }
+
+    fn create_offspring(&self, parent_fitness_pairs: &[FitnessPair], crossover_pop: &[FitnessPair]) -> Vec<CrossoverFamily> {
         let mut rng = rand::thread_rng();
         let mut children: Vec<CrossoverFamily> = Vec::with_capacity(crossover_pop.len());
 
         crossover_pop.iter().for_each(|pair| {
-            let mut p_a_i = rng.gen_range(0..parent_fitness_pairs.len());
-            for _ in 1..self.rounds {
-                let challenger = rng.gen_range(0..parent_fitness_pairs.len());
-                if parent_fitness_pairs[challenger].fitness > parent_fitness_pairs[p_a_i].fitness {
-                    p_a_i = challenger;
-                }
-            }
-
-            let mut p_b_i = rng.gen_range(0..parent_fitness_pairs.len());
-            for _ in 1..self.rounds {
-                let challenger = rng.gen_range(0..parent_fitness_pairs.len());
-                if parent_fitness_pairs[challenger].fitness > parent_fitness_pairs[p_b_i].fitness {
-                    p_b_i = challenger;
+            let parents = (0..self.parents).map(|_| {
+                let mut p_i = rng.gen_range(0..parent_fitness_pairs.len());
+                for _ in 1..self.rounds {
+                    let challenger = rng.gen_range(0..parent_fitness_pairs.len());
+                    if parent_fitness_pairs[challenger].fitness > parent_fitness_pairs[p_i].fitness {
+                        p_i = challenger;
+                    }
                 }
-            }
-
-            let p_a = &parent_fitness_pairs[p_a_i];
-            let p_b = &parent_fitness_pairs[p_b_i];
+                parent_fitness_pairs[p_i].clone()
+            }).collect();
 
             children.push(CrossoverFamily { 
                 child_index: pair.index,
-                parent_a_index: p_a.index,
-                parent_b_index: p_b.index,
-                parent_a_fitness: p_a.fitness, 
-                parent_b_fitness: p_b.fitness,
+                parents,
             });
         });
 
@@ -94,11 +164,13 @@ impl ParentSelectionStrategy for TournamentStrategy {
 }
 
 /// Randomly selects from the top percent of parents 
-/// Top percent is defined by the rate variable
+/// Top percent is defined by the rate variable.
+/// Parents within a family are distinct while the top percent is large enough
 #[derive(Clone)]
 pub struct PrimeParentStrategy {
     pub weight: usize,
-    pub rate: f64
+    pub rate: f64,
+    pub parents: usize
 }
 
 impl ParentSelectionStrategy for PrimeParentStrategy {
@@ -106,27 +178,28 @@ impl ParentSelectionStrategy for PrimeParentStrategy {
         self.weight
     }
 
-    fn create_offspring(&self, parent_fitness_pairs: &Vec<FitnessPair>, crossover_pop: &[FitnessPair]) -> Vec<CrossoverFamily> {
+    fn get_parent_count(&self) -> usize {
+        self.parents
+    }
+
+    fn create_offspring(&self, parent_fitness_pairs: &[FitnessPair], crossover_pop: &[FitnessPair]) -> Vec<CrossoverFamily> {
         let mut rng = rand::thread_rng();
         let mut children: Vec<CrossoverFamily> = Vec::with_capacity(crossover_pop.len());
         let prime_parent_count = (parent_fitness_pairs.len() as f64 * self.rate).max(1.0) as usize;
+        let prime_range = (parent_fitness_pairs.len() - prime_parent_count)..parent_fitness_pairs.len();
 
         crossover_pop.iter().for_each(|pair| {
-            let p_a_i = rng.gen_range((parent_fitness_pairs.len() - prime_parent_count)..parent_fitness_pairs.len());
-            let mut p_b_i = rng.gen_range((parent_fitness_pairs.len() - prime_parent_count)..parent_fitness_pairs.len());
-            while p_a_i == p_b_i {
-                p_b_i = rng.gen_range((parent_fitness_pairs.len() - prime_parent_count)..parent_fitness_pairs.len());
+            let mut parent_indices: Vec<usize> = Vec::with_capacity(self.parents);
+            while parent_indices.len() < self.parents {
+                let p_i = rng.gen_range(prime_range.clone());
+                if !parent_indices.contains(&p_i) || parent_indices.len() >= prime_parent_count {
+                    parent_indices.push(p_i);
+                }
             }
             
-            let p_a = &parent_fitness_pairs[p_a_i];
-            let p_b = &parent_fitness_pairs[p_b_i];
-            
             children.push(CrossoverFamily { 
                 child_index: pair.index,
-                parent_a_index: p_a.index,
-                parent_b_index: p_b.index,
-                parent_a_fitness: p_a.fitness, 
-                parent_b_fitness: p_b.fitness,
+                parents: parent_indices.iter().map(|p_i| parent_fitness_pairs[*p_i].clone()).collect(),
             });
         });
 
@@ -135,10 +208,13 @@ impl ParentSelectionStrategy for PrimeParentStrategy {
 }
 
 /// Randomly selects parents weighted by the fitness ratio of
-/// the parent compared to all other parents
+/// the parent compared to all other parents.
+/// Expects non negative fitness, see `FitnessScaling`. Falls back
+/// to uniform selection when the fitness sum is not positive
 #[derive(Clone)]
 pub struct RouletteStrategy {
-    pub weight: usize
+    pub weight: usize,
+    pub parents: usize
 }
 
 impl ParentSelectionStrategy for RouletteStrategy {
@@ -146,27 +222,39 @@ impl ParentSelectionStrategy for RouletteStrategy {
         self.weight
     }
 
-    fn create_offspring(&self, parent_fitness_pairs: &Vec<FitnessPair>, crossover_pop: &[FitnessPair]) -> Vec<CrossoverFamily> {
-        let fitness_sum = parent_fitness_pairs.iter().fold(0.0, |sum, pair| sum + pair.fitness);
+    fn get_parent_count(&self) -> usize {
+        self.parents
+    }
+
+    fn create_offspring(&self, parent_fitness_pairs: &[FitnessPair], crossover_pop: &[FitnessPair]) -> Vec<CrossoverFamily> {
+        let fitness_sum = parent_fitness_pairs.iter().fold(0.0, |sum, pair| sum + pair.fitness.max(0.0));
         let mut rng = rand::thread_rng();
         let mut children: Vec<CrossoverFamily> = Vec::with_capacity(crossover_pop.len());
 
-        crossover_pop.iter().for_each(|pair| {
-            let p_a_i = (rng.gen_range(0.0..fitness_sum) * parent_fitness_pairs.len() as f64) as usize;
-            let p_b_i = (rng.gen_range(0.0..fitness_sum) * parent_fitness_pairs.len() as f64) as usize;
+        let mut spin = || -> usize {
+            if fitness_sum <= 0.0 {
+                return rng.gen_range(0..parent_fitness_pairs.len());
+            }
 
-            let p_a = &parent_fitness_pairs[p_a_i];
-            let p_b = &parent_fitness_pairs[p_b_i];
+            let mut ball = rng.gen_range(0.0..fitness_sum);
+            for (i, pair) in parent_fitness_pairs.iter().enumerate() {
+                ball -= pair.fitness.max(0.0);
+                if ball < 0.0 {
+                    return i;
+                }
+            }
+            parent_fitness_pairs.len() - 1
+        };
+
+        crossover_pop.iter().for_each(|pair| {
+            let parents = (0..self.parents).map(|_| parent_fitness_pairs[spin()].clone()).collect();
 
             children.push(CrossoverFamily { 
                 child_index: pair.index,
-                parent_a_index: p_a.index,
-                parent_b_index: p_b.index,
-                parent_a_fitness: p_a.fitness, 
-                parent_b_fitness: p_b.fitness,
+                parents,
             })
         });
 
         children
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,249 @@
+use rand::{rngs::ThreadRng, seq::index, Rng};
+use crate::evonet::EvoNet;
+use super::{
+    evotrainer::HasFitness,
+    objective::Objective,
+    statistics::GenerationStats,
+    trainer_builder::TrainerBuildError
+};
+
+/// How the donor vector of each target is built, all using binomial crossover
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DeVariant {
+    /// DE/rand/1/bin
+    #[default]
+    RandOne,
+    /// DE/best/1/bin
+    BestOne,
+    /// DE/current-to-best/1/bin
+    CurrentToBest,
+}
+
+/// Control of the F and CR factors
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DeAdaptation {
+    /// The configured factors are used for every individual
+    #[default]
+    Static,
+    /// jDE, each individual carries its own factors which are
+    /// resampled before building its trial (tau_f, tau_cr)
+    Jde(f64, f64),
+}
+
+/// Differential evolution over a population of nets of one architecture
+pub struct DeTrainer {
+    population: Vec<EvoNet>,
+    factors: Vec<(f64, f64)>,
+    fitness_fn: fn(&mut EvoNet) -> f64,
+    objective: Objective,
+    variant: DeVariant,
+    adaptation: DeAdaptation,
+    generation: usize,
+    statistics: Vec<GenerationStats>
+}
+
+impl DeTrainer {
+    pub fn get_statistics(&self) -> &[GenerationStats] {
+        &self.statistics
+    }
+
+    pub fn get_population(&self) -> &[EvoNet] {
+        &self.population
+    }
+
+    /// Current (F, CR) of each individual
+    pub fn get_factors(&self) -> &[(f64, f64)] {
+        &self.factors
+    }
+
+    pub fn extract_best(&self) -> EvoNet {
+        self.population[self.best_index()].clone()
+    }
+
+    pub fn train(&mut self, generations: usize) {
+        let mut rng = rand::thread_rng();
+        if self.generation == 0 {
+            self.population.iter_mut().for_each(|net| {
+                let fitness = (self.fitness_fn)(net);
+                net.set_fitness(fitness);
+            });
+        }
+
+        (0..generations).for_each(|_| {
+            let weights: Vec<Vec<f64>> = self.population.iter().map(|net| net.get_weights()).collect();
+            let best = self.best_index();
+
+            for target in 0..self.population.len() {
+                let (f, cr) = self.sample_factors(target, &mut rng);
+                let donor = self.donor(target, best, f, &weights, &mut rng);
+
+                let forced = rng.gen_range(0..donor.len());
+                let trial_weights: Vec<f64> = (0..donor.len()).map(|i| {
+                    if i == forced || rng.gen_range(0.0..1.0) < cr { donor[i] } else { weights[target][i] }
+                }).collect();
+
+                let mut trial = self.population[target].clone();
+                trial.set_weights(&trial_weights);
+                let fitness = (self.fitness_fn)(&mut trial);
+                trial.set_fitness(fitness);
+
+                if !self.objective.is_better(self.population[target].get_fitness(), fitness) {
+                    self.population[target] = trial;
+                    self.factors[target] = (f, cr);
+                }
+            }
+
+            self.record_generation();
+            self.generation += 1;
+        });
+    }
+
+    fn sample_factors(&self, target: usize, rng: &mut ThreadRng) -> (f64, f64) {
+        let (f, cr) = self.factors[target];
+        match self.adaptation {
+            DeAdaptation::Static => (f, cr),
+            DeAdaptation::Jde(tau_f, tau_cr) => {
+                let f = if rng.gen_range(0.0..1.0) < tau_f { 0.1 + 0.9 * rng.gen_range(0.0..1.0) } else { f };
+                let cr = if rng.gen_range(0.0..1.0) < tau_cr { rng.gen_range(0.0..1.0) } else { cr };
+                (f, cr)
+            },
+        }
+    }
+
+    fn donor(&self, target: usize, best: usize, f: f64, weights: &[Vec<f64>], rng: &mut ThreadRng) -> Vec<f64> {
+        // Three distinct individuals other than the target
+        let picks: Vec<usize> = index::sample(rng, weights.len() - 1, 3)
+            .into_iter()
+            .map(|i| if i >= target { i + 1 } else { i })
+            .collect();
+        let (r1, r2, r3) = (&weights[picks[0]], &weights[picks[1]], &weights[picks[2]]);
+        let current = &weights[target];
+        let best = &weights[best];
+
+        (0..current.len()).map(|i| match self.variant {
+            DeVariant::RandOne => r1[i] + f * (r2[i] - r3[i]),
+            DeVariant::BestOne => best[i] + f * (r1[i] - r2[i]),
+            DeVariant::CurrentToBest => current[i] + f * (best[i] - current[i]) + f * (r1[i] - r2[i]),
+        }).collect()
+    }
+
+    fn best_index(&self) -> usize {
+        (0..self.population.len())
+            .reduce(|best, i| if self.objective.is_better(self.population[i].get_fitness(), self.population[best].get_fitness()) { i } else { best })
+            .unwrap()
+    }
+
+    fn record_generation(&mut self) {
+        let fitnesses: Vec<f64> = self.population.iter().map(|net| net.get_fitness()).collect();
+        self.statistics.push(GenerationStats::from_fitnesses(self.generation, &fitnesses, self.objective));
+    }
+}
+
+pub struct DeBuilder<'a> {
+    population_size: Option<usize>,
+    architecture: Option<&'a [usize]>,
+    fitness_function: Option<fn(&mut EvoNet) -> f64>,
+    objective: Objective,
+    variant: DeVariant,
+    adaptation: DeAdaptation,
+    differential_weight: f64,
+    crossover_rate: f64,
+}
+
+impl Default for DeBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a> DeBuilder<'a> {
+
+    pub fn new() -> Self {
+        Self {
+            population_size: None,
+            architecture: None,
+            fitness_function: None,
+            objective: Objective::Maximize,
+            variant: DeVariant::default(),
+            adaptation: DeAdaptation::default(),
+            differential_weight: 0.5,
+            crossover_rate: 0.9,
+        }
+    }
+
+    pub fn build(&self) -> Result<DeTrainer, TrainerBuildError> {
+        let pop_size = self.population_size.ok_or(TrainerBuildError::VariableNotSet(String::from("population_size not set")))?;
+        let arch = self.architecture.ok_or(TrainerBuildError::VariableNotSet(String::from("architecture not set")))?;
+        let ft_fn = self.fitness_function.ok_or(TrainerBuildError::VariableNotSet(String::from("fitness_function not set")))?;
+
+        if pop_size < 4 {
+            return Err(TrainerBuildError::ValidationError(String::from("differential evolution needs a population_size of at least 4")));
+        }
+
+        if arch.contains(&0) {
+            return Err(TrainerBuildError::ValidationError(String::from("architecure cannot contain 0's")));
+        }
+
+        if !(0.0..=2.0).contains(&self.differential_weight) {
+            return Err(TrainerBuildError::ValidationError(String::from("differential_weight must be between 0.0..=2.0")));
+        }
+
+        if !(0.0..=1.0).contains(&self.crossover_rate) {
+            return Err(TrainerBuildError::ValidationError(String::from("crossover_rate must be between 0.0..=1.0")));
+        }
+
+        if let DeAdaptation::Jde(tau_f, tau_cr) = self.adaptation {
+            if !(0.0..=1.0).contains(&tau_f) || !(0.0..=1.0).contains(&tau_cr) {
+                return Err(TrainerBuildError::ValidationError(String::from("jDE rates must be between 0.0..=1.0")));
+            }
+        }
+
+        Ok(DeTrainer {
+            population: (0..pop_size).map(|_| EvoNet::new(arch)).collect(),
+            factors: vec![(self.differential_weight, self.crossover_rate); pop_size],
+            fitness_fn: ft_fn,
+            objective: self.objective,
+            variant: self.variant,
+            adaptation: self.adaptation,
+            generation: 0,
+            statistics: Vec::new(),
+        })
+    }
+
+    pub fn set_population_size(&mut self, size: usize) {
+        self.population_size = Some(size);
+    }
+
+    pub fn set_architecture(&mut self, architecture: &'a[usize]) {
+        self.architecture = Some(architecture);
+    }
+
+    pub fn set_fitness_function(&mut self, fit_fn: fn(&mut EvoNet) -> f64) {
+        self.fitness_function = Some(fit_fn);
+    }
+
+    /// Defaults to `Objective::Maximize`
+    pub fn set_objective(&mut self, objective: Objective) {
+        self.objective = objective;
+    }
+
+    /// Defaults to `DeVariant::RandOne`
+    pub fn set_variant(&mut self, variant: DeVariant) {
+        self.variant = variant;
+    }
+
+    /// Defaults to `DeAdaptation::Static`
+    pub fn set_adaptation(&mut self, adaptation: DeAdaptation) {
+        self.adaptation = adaptation;
+    }
+
+    /// F, scale of the difference vectors, and the starting value under jDE. Defaults to 0.5
+    pub fn set_differential_weight(&mut self, weight: f64) {
+        self.differential_weight = weight;
+    }
+
+    /// CR, chance a weight is taken from the donor, and the starting value under jDE. Defaults to 0.9
+    pub fn set_crossover_rate(&mut self, rate: f64) {
+        self.crossover_rate = rate;
+    }
+}
@@ -1,18 +1,64 @@
 use std::collections::BinaryHeap;
-use crate::evonet::EvoNet;
-use super::crossover::{ParentSelectionStrategy, Strategies};
-
-pub struct EvoTrainer {
-    population: Vec<EvoNet>,
-    fitness_fn: fn(&mut EvoNet) -> f64,
-    survival_rate: f64,
-    crossover_rate: f64,
-    mutation_rate: f64,
+use rand::{rngs::StdRng, SeedableRng};
+use crate::{evonet::EvoNet, genome::Genome};
+use super::{
+    adaptive::{OperatorAdaptation, OperatorPool},
+    crossover::{CrossoverFamily, CrossoverOperator, ParentSelectionStrategy, Strategies},
+    initialization::WeightInit,
+    mating::{Lineage, MatingPolicy},
+    mutation::MutationOperator,
+    novelty::{NoveltyArchive, NoveltyConfig},
+    objective::Objective,
+    scaling::FitnessScaling,
+    speciation::{SpeciationConfig, SpeciesTracker},
+    statistics::GenerationStats
+};
+
+pub struct EvoTrainer<G: Genome = EvoNet> {
+    population: Vec<G>,
+    fitness_fn: fn(&mut G) -> f64,
+    params: TrainerParams<G>,
     crossover_strategies: Vec<Box<dyn ParentSelectionStrategy>>,
-    crossover_weight_sum: usize
+    strategy_pool: OperatorPool,
+    crossover_pool: OperatorPool,
+    mutation_pool: OperatorPool,
+    /// Mutation operator applied to each member and its fitness before the mutation
+    pending_mutations: Vec<Option<(usize, f64)>>,
+    lineage: Vec<Lineage>,
+    next_id: usize,
+    species: Option<SpeciesTracker<G>>,
+    novelty: Option<NoveltyArchive<G>>,
+    generation: usize,
+    statistics: Vec<GenerationStats>
 }
 
-#[derive(Debug)]
+/// Validated settings the trainer runs with, see `TrainerBuilder`
+#[derive(Clone, Debug)]
+pub struct TrainerParams<G: Genome = EvoNet> {
+    pub survival_rate: f64,
+    pub crossover_rate: f64,
+    pub mutation_rate: f64,
+    pub fitness_scaling: FitnessScaling,
+    pub objective: Objective,
+    pub mating_policy: MatingPolicy,
+    /// (operator, weight)
+    pub crossover_operators: Vec<(CrossoverOperator, usize)>,
+    /// (operator, weight)
+    pub mutation_operators: Vec<(MutationOperator, usize)>,
+    pub operator_adaptation: OperatorAdaptation,
+    pub speciation: Option<SpeciationConfig>,
+    pub novelty: Option<NoveltyConfig<G>>,
+    pub genome_config: G::Config,
+    /// Scheme of each weight layer of the first population
+    pub weight_init: Vec<WeightInit>,
+    /// Seed of the weight initialisation, `None` draws from entropy
+    pub seed: Option<u64>,
+}
+
+/// Selection value of a population member. `fitness` is oriented by the
+/// trainer's `Objective` so a bigger value is always better, letting the
+/// ordering and the selection strategies ignore the optimisation direction
+#[derive(Clone, Debug)]
 pub struct FitnessPair {
     pub fitness: f64,
     pub index: usize
@@ -44,46 +90,46 @@ impl PartialEq for FitnessPair {
 
 impl Eq for FitnessPair {}
 
-impl EvoTrainer {
+impl <G: Genome> EvoTrainer<G> {
 
     pub fn initialize(
         population_size: usize,
         architecture: &[usize],
-        fitness_fn: fn(&mut EvoNet) -> f64,
-        survival_rate: f64,
-        crossover_rate: f64,
-        mutation_rate: f64,
+        fitness_fn: fn(&mut G) -> f64,
+        params: TrainerParams<G>,
         strategies: Vec<Strategies>
     ) -> Self {
         let mut pop_vec = Vec::with_capacity(population_size);
-        Self::spawn_population(&mut pop_vec, architecture, fitness_fn);
+        Self::spawn_population(&mut pop_vec, architecture, &params, fitness_fn);
         
         let mut parent_strats: Vec<Box<dyn ParentSelectionStrategy>> = Vec::with_capacity(strategies.len());
-        let crossover_weight_sum = strategies.iter().fold(0, |sum, s| {
-            sum + match s {
-                Strategies::Tournement(strat) => {
-                    parent_strats.push(Box::new(strat.clone()));
-                    sum + strat.get_weight()
-                },
-                Strategies::PrimeParent(strat) => {
-                    parent_strats.push(Box::new(strat.clone()));
-                    sum + strat.get_weight()
-                },
-                Strategies::Roulette(strat) => {
-                    parent_strats.push(Box::new(strat.clone()));
-                    sum + strat.get_weight()
-                }
+        strategies.iter().for_each(|s| {
+            match s {
+                Strategies::Tournement(strat) => parent_strats.push(Box::new(strat.clone())),
+                Strategies::PrimeParent(strat) => parent_strats.push(Box::new(strat.clone())),
+                Strategies::Roulette(strat) => parent_strats.push(Box::new(strat.clone())),
             }
         });
 
+        let strategy_weights: Vec<usize> = parent_strats.iter().map(|s| s.get_weight()).collect();
+        let crossover_weights: Vec<usize> = params.crossover_operators.iter().map(|(_, w)| *w).collect();
+        let mutation_weights: Vec<usize> = params.mutation_operators.iter().map(|(_, w)| *w).collect();
+
         Self { 
             population: pop_vec,
             fitness_fn,
-            survival_rate,
-            crossover_rate,
-            mutation_rate,
             crossover_strategies: parent_strats,
-            crossover_weight_sum,
+            strategy_pool: OperatorPool::new(&strategy_weights),
+            crossover_pool: OperatorPool::new(&crossover_weights),
+            mutation_pool: OperatorPool::new(&mutation_weights),
+            pending_mutations: vec![None; population_size],
+            lineage: (0..population_size).map(Lineage::new).collect(),
+            next_id: population_size,
+            species: params.speciation.map(SpeciesTracker::new),
+            novelty: params.novelty.map(NoveltyArchive::new),
+            generation: 0,
+            statistics: Vec::new(),
+            params,
         }
     }
 
@@ -93,20 +139,14 @@ impl EvoTrainer {
         });
     }
 
-    pub fn show_individual(&self, index: usize) -> Result<(), ()> {
-        match self.population.get(index) {
-            Some(net) => {
-                println!("{}", net); 
-                Ok(())
-            },
-            None => Err(()),
-        }
+    pub fn show_individual(&self, index: usize) -> Option<()> {
+        self.population.get(index).map(|net| println!("{}", net))
     }
 
-    pub fn extract_best(&self) -> EvoNet {
-        let mut ex_net: &EvoNet = self.population.first().unwrap();
+    pub fn extract_best(&self) -> G {
+        let mut ex_net: &G = self.population.first().unwrap();
         self.population.iter().for_each(|net| {
-            if net.get_fitness() > ex_net.get_fitness() {
+            if self.params.objective.is_better(net.get_fitness(), ex_net.get_fitness()) {
                 ex_net = net;
             }
         });
@@ -114,54 +154,198 @@ impl EvoTrainer {
         ex_net.clone()
     }
 
+    pub fn get_objective(&self) -> Objective {
+        self.params.objective
+    }
+
+    pub fn get_population_size(&self) -> usize {
+        self.population.len()
+    }
+
+    /// Clones of the `count` best members of the population, best first
+    pub fn extract_top(&self, count: usize) -> Vec<G> {
+        let mut ranked: Vec<&G> = self.population.iter().collect();
+        ranked.sort_by(|a, b| self.params.objective.compare(b.get_fitness(), a.get_fitness()));
+        ranked.into_iter().take(count).cloned().collect()
+    }
+
+    /// Replaces the worst members of the population with the given nets.
+    /// At least one original member is always kept
+    pub fn insert_members(&mut self, members: Vec<G>) {
+        let mut ranked: Vec<usize> = (0..self.population.len()).collect();
+        ranked.sort_by(|a, b| self.params.objective.compare(self.population[*a].get_fitness(), self.population[*b].get_fitness()));
+
+        for (slot, member) in ranked.into_iter().take(self.population.len() - 1).zip(members) {
+            self.population[slot] = member;
+            self.pending_mutations[slot] = None;
+            self.lineage[slot] = Lineage::new(self.next_id);
+            self.next_id += 1;
+        }
+    }
+
+    /// Statistics of every generation trained so far
+    pub fn get_statistics(&self) -> &[GenerationStats] {
+        &self.statistics
+    }
+
     pub fn train(&mut self, generations: usize) {
         (0..generations).for_each(|_| {
+            // Mutating at the start rather than the end of a generation keeps the
+            // fitness of the population current for extract_best and migration
+            if self.generation > 0 {
+                self.mutate_population();
+            }
             let mut pop_fitness = self.calculate_pop_fitness();
-            self.create_next_gen(&mut pop_fitness, self.survival_rate);
-            self.mutate_population();
+            let mut stats = self.summarize_generation();
+            stats.novelty_archive_size = self.get_novelty_archive().len();
+            self.params.fitness_scaling.apply(&mut pop_fitness);
+
+            if let Some(tracker) = self.species.as_mut() {
+                tracker.speciate(&self.population, self.params.objective);
+                stats.species_sizes = tracker.get_species().iter().map(|s| s.members.len()).collect();
+                self.create_next_gen_speciated(&pop_fitness, self.params.survival_rate);
+            } else {
+                self.create_next_gen(&mut pop_fitness, self.params.survival_rate);
+            }
+
+            self.strategy_pool.update(self.params.operator_adaptation);
+            self.crossover_pool.update(self.params.operator_adaptation);
+            self.mutation_pool.update(self.params.operator_adaptation);
+            stats.strategy_weights = self.strategy_pool.get_probabilities().to_vec();
+            stats.crossover_operator_weights = self.crossover_pool.get_probabilities().to_vec();
+            stats.mutation_operator_weights = self.mutation_pool.get_probabilities().to_vec();
+            self.statistics.push(stats);
+            self.generation += 1;
         });
     }
 
+    /// Evaluates the population and returns its selection values. With novelty
+    /// search enabled the selection value blends fitness with behavioural novelty,
+    /// each net still reports its raw fitness
     pub fn calculate_pop_fitness(&mut self) -> Vec<FitnessPair> {
-        let mut fitnesses: BinaryHeap<FitnessPair> = BinaryHeap::new();
+        let mut selection: Vec<f64> = Vec::with_capacity(self.population.len());
         for (i, net) in self.population.iter_mut().enumerate() {
+            // Every evaluation starts from a clean recurrent state
+            net.reset_state();
             let ft_score = (self.fitness_fn)(net);
             net.set_fitness(ft_score);
-            fitnesses.push(FitnessPair { fitness: ft_score, index: i })
+            if let Some((operator, pre_mutation_fitness)) = self.pending_mutations[i].take() {
+                self.mutation_pool.record(operator, self.params.objective.is_better(ft_score, pre_mutation_fitness));
+            }
+            selection.push(self.params.objective.orient(ft_score));
         }
+
+        if let Some(archive) = self.novelty.as_mut() {
+            let behaviours = archive.describe(&mut self.population);
+            let novelty = archive.score(&behaviours);
+            selection = archive.blend(&selection, &novelty);
+        }
+
+        let mut fitnesses: BinaryHeap<FitnessPair> = BinaryHeap::new();
+        selection.into_iter().enumerate().for_each(|(i, fitness)| fitnesses.push(FitnessPair { fitness, index: i }));
         //Sorted from low fitness to high fitness
-        return fitnesses.into_sorted_vec();
+        fitnesses.into_sorted_vec()
+    }
+
+    /// Behaviours archived by novelty search, empty when it is disabled
+    pub fn get_novelty_archive(&self) -> &[Vec<f64>] {
+        match self.novelty.as_ref() {
+            Some(archive) => archive.get_archive(),
+            None => &[],
+        }
     }
 
     fn create_next_gen(&mut self, fitness_pairs: &mut Vec<FitnessPair>, survival_rate: f64) {
-        let dead_pop: Vec<_> = fitness_pairs.drain(0..(fitness_pairs.len() as f64 * (1.0 - survival_rate)) as usize).collect();
-        let (crossover_pop, copy_pop) = dead_pop.split_at((dead_pop.len() as f64 * self.crossover_rate) as usize);
+        // At least one individual has to survive to parent the next generation
+        let dead_count = ((fitness_pairs.len() as f64 * (1.0 - survival_rate)) as usize).min(fitness_pairs.len() - 1);
+        let dead_pop: Vec<_> = fitness_pairs.drain(0..dead_count).collect();
+        let (crossover_pop, copy_pop) = dead_pop.split_at((dead_pop.len() as f64 * self.params.crossover_rate) as usize);
         self.crossover(fitness_pairs, crossover_pop);
+        self.generate_from_copy(fitness_pairs, copy_pop);
+    }
+
+    /// Reproduces each species separately. Species receive offspring slots by
+    /// their shared fitness, their survivors keep their slots and parent the
+    /// species' offspring, every other slot is freed for the offspring
+    fn create_next_gen_speciated(&mut self, fitness_pairs: &[FitnessPair], survival_rate: f64) {
+        let tracker = self.species.as_ref().unwrap();
+        let quotas = tracker.allocate_offspring(fitness_pairs, self.params.objective);
+
+        let mut member_of = vec![0; fitness_pairs.len()];
+        tracker.get_species().iter().enumerate().for_each(|(s, species)| {
+            species.members.iter().for_each(|i| member_of[*i] = s);
+        });
+
+        // Members of each species sorted from low fitness to high fitness
+        let mut species_pairs: Vec<Vec<FitnessPair>> = vec![Vec::new(); quotas.len()];
+        fitness_pairs.iter().for_each(|pair| species_pairs[member_of[pair.index]].push(pair.clone()));
+
+        let mut dead_pop: Vec<FitnessPair> = Vec::with_capacity(fitness_pairs.len());
+        let mut survivors: Vec<Vec<FitnessPair>> = Vec::with_capacity(quotas.len());
+        for (s, pairs) in species_pairs.iter_mut().enumerate() {
+            let survivor_count = if quotas[s] == 0 {
+                0
+            } else {
+                ((quotas[s] as f64 * survival_rate) as usize).clamp(1, quotas[s].min(pairs.len()))
+            };
+            dead_pop.extend(pairs.drain(0..pairs.len() - survivor_count));
+            survivors.push(pairs.clone());
+        }
 
-        todo!("Generate from copy");
-        // self.generate_from_copy(fitness_pairs, copy_pop);
+        for (s, parents) in survivors.iter().enumerate() {
+            let offspring: Vec<FitnessPair> = dead_pop.drain(0..quotas[s] - parents.len()).collect();
+            let (crossover_pop, copy_pop) = offspring.split_at((offspring.len() as f64 * self.params.crossover_rate) as usize);
+            self.crossover(parents, crossover_pop);
+            self.generate_from_copy(parents, copy_pop);
+        }
     }
 
-    fn crossover(&mut self, fitness_pairs: &mut Vec<FitnessPair>, crossover_pop: &[FitnessPair]) {
+    fn crossover(&mut self, fitness_pairs: &[FitnessPair], crossover_pop: &[FitnessPair]) {
         let mut i: usize = 0;
-        for strat in self.crossover_strategies.iter() {
-            let j = (((strat.get_weight() as f64) / (self.crossover_weight_sum as f64)) * crossover_pop.len() as f64) as usize;
-            let families = strat.create_offspring(
+        let mut probability_sum: f64 = 0.0;
+        // (strategy index, family)
+        let mut families: Vec<(usize, CrossoverFamily)> = Vec::with_capacity(crossover_pop.len());
+        for (s, strat) in self.crossover_strategies.iter().enumerate() {
+            probability_sum += self.strategy_pool.get_probabilities()[s];
+            let j = if s == self.crossover_strategies.len() - 1 {
+                crossover_pop.len()
+            } else {
+                ((probability_sum * crossover_pop.len() as f64) as usize).clamp(i, crossover_pop.len())
+            };
+            let strat_families = strat.create_offspring(
                 fitness_pairs, 
                 &crossover_pop[i..j]
             );
 
-            for family in families.iter() {
-                self.population[family.child_index] = self.create_child(
-                    family.parent_a_index,
-                    family.parent_b_index, 
-                    family.parent_a_fitness,
-                    family.parent_b_fitness
-                );
+            for (family, pair) in strat_families.into_iter().zip(crossover_pop[i..j].iter()) {
+                families.push((s, self.params.mating_policy.restrict(
+                    family,
+                    || strat.create_offspring(fitness_pairs, std::slice::from_ref(pair)).remove(0),
+                    &self.population,
+                    &self.lineage
+                )));
             }
 
             i = j;
         }
+
+        let mut rng = rand::thread_rng();
+        for (s, family) in families.iter() {
+            let operator = self.crossover_pool.sample(&mut rng);
+            let parent_indices: Vec<usize> = family.parents.iter().map(|p| p.index).collect();
+            let best_parent_fitness = parent_indices.iter()
+                .map(|p| self.population[*p].get_fitness())
+                .reduce(|best, ft| if self.params.objective.is_better(ft, best) { ft } else { best })
+                .unwrap();
+
+            let child = self.create_child(family, self.params.crossover_operators[operator].0);
+            let success = self.params.objective.is_better(child.get_fitness(), best_parent_fitness);
+            self.strategy_pool.record(*s, success);
+            self.crossover_pool.record(operator, success);
+
+            self.population[family.child_index] = child;
+            self.lineage[family.child_index] = self.create_lineage(&parent_indices);
+        }
     }
 
     // fn generate_from_rank_crossover(&mut self, fitness_pairs: &Vec<FitnessPair>, crossover_pop: &[FitnessPair]) {
@@ -187,26 +371,28 @@ impl EvoTrainer {
     //     });
     // }
 
-    // fn generate_from_copy(&mut self, fitness_pairs: &Vec<FitnessPair>, copy_pop: &[FitnessPair]) {
-    //     let prime_parent_count = (fitness_pairs.len() as f64 * self.params.prime_parent_rate).max(1.0) as usize;
-    //     let mut i: usize = 1;
-        
-    //     // let pop_deviation = Self::calc_std_deviation(&fitness_pairs);
-    //     // let ratio = pop_deviation / self.params.std_deviation;
-    //     // let mut_variance = 1.0 - 1.0_f64.min(ratio);
-    //     let mut_variance = 0.0;
-
-    //     copy_pop.iter().for_each(|pair| {
-    //         let parent = &fitness_pairs[fitness_pairs.len() - i];
-    //         i += 1;
-    //         if i >= prime_parent_count {
-    //             i = 1;
-    //         }
-    //         let mut child = self.population[parent.index].clone();
-    //         child.mutate(self.params.mutation_frequency + mut_variance);
-    //         self.population[pair.index] = child;
-    //     });
-    // }
+    /// Replaces the copy population with clones of the survivors,
+    /// cycling from the fittest survivor downwards
+    fn generate_from_copy(&mut self, fitness_pairs: &[FitnessPair], copy_pop: &[FitnessPair]) {
+        let mut i: usize = 1;
+
+        copy_pop.iter().for_each(|pair| {
+            let parent = &fitness_pairs[fitness_pairs.len() - i];
+            i += 1;
+            if i > fitness_pairs.len() {
+                i = 1;
+            }
+            self.population[pair.index] = self.population[parent.index].clone();
+            self.lineage[pair.index] = self.create_lineage(&[parent.index]);
+        });
+    }
+
+    fn create_lineage(&mut self, parent_indices: &[usize]) -> Lineage {
+        let parents: Vec<&Lineage> = parent_indices.iter().map(|i| &self.lineage[*i]).collect();
+        let lineage = Lineage::from_parents(self.next_id, &parents, self.params.mating_policy.lineage_depth());
+        self.next_id += 1;
+        lineage
+    }
 
     // fn generate_from_tournament_crossover(&mut self, fitness_pairs: &Vec<FitnessPair>, crossover_pop: &[FitnessPair]) {
     //     let mut rng = rand::thread_rng();
@@ -244,9 +430,12 @@ impl EvoTrainer {
         
     // }
 
-    fn spawn_population(pop_vec: &mut Vec<EvoNet>, architecture: &[usize], fitness_fn: fn(&mut EvoNet) -> f64) {
+    fn spawn_population(pop_vec: &mut Vec<G>, architecture: &[usize], params: &TrainerParams<G>, fitness_fn: fn(&mut G) -> f64) {
+        let mut rng = params.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
         (0..pop_vec.capacity()).for_each(|_| {
-            let mut spawn = EvoNet::new(architecture);
+            let mut spawn = G::spawn(architecture, &params.genome_config);
+            spawn.initialize_weights(&params.weight_init, &mut rng);
+            spawn.reset_state();
             let spawn_fit = (fitness_fn)(&mut spawn);
             spawn.set_fitness(spawn_fit);
             pop_vec.push(
@@ -255,10 +444,11 @@ impl EvoTrainer {
         })
     }
 
-    fn create_child(&self, parent_a_idx: usize, parent_b_idx: usize, p1_fitness: f64, p2_fitness: f64) -> EvoNet {
-        let p_a = self.population.get(parent_a_idx).unwrap();
-        let p_b = self.population.get(parent_b_idx).unwrap();
-        let mut child = EvoNet::from_parents(p_a, p_b, p1_fitness, p2_fitness);
+    fn create_child(&self, family: &CrossoverFamily, operator: CrossoverOperator) -> G {
+        let parents: Vec<&G> = family.parents.iter().map(|p| &self.population[p.index]).collect();
+        let fitnesses: Vec<f64> = family.parents.iter().map(|p| p.fitness).collect();
+        let mut child = G::from_many_parents(&parents, &fitnesses, operator);
+        child.reset_state();
         let c_fit = (self.fitness_fn)(&mut child);
         child.set_fitness(c_fit);
         child
@@ -270,20 +460,18 @@ impl EvoTrainer {
         // let ratio = 1.0;
         // let mut_variance = 1.0 - 1.0_f64.min(ratio);
 
-        self.population.iter_mut().for_each(|net| 
-            net.mutate(self.mutation_rate)
-        )
+        let mut rng = rand::thread_rng();
+        for (i, net) in self.population.iter_mut().enumerate() {
+            let operator = self.mutation_pool.sample(&mut rng);
+            self.pending_mutations[i] = Some((operator, net.get_fitness()));
+            net.mutate_with(self.params.mutation_rate, self.params.mutation_operators[operator].0);
+        }
     }
 
-    fn calc_std_deviation<T: HasFitness>(data: &Vec<T>) -> f64 {
-        let n = data.len() as f64;
-    
-        let (sum, sum_sq) = data.iter().fold((0.0, 0.0), |(sum, sum_sq), pair| {
-            (sum + pair.get_fitness(), sum_sq + pair.get_fitness().powf(2.0))
-        });
-
-        let mean = sum / n;
-        ((sum_sq / n) - (mean * mean)).sqrt()
+    /// Raw fitness statistics of the freshly evaluated population
+    fn summarize_generation(&self) -> GenerationStats {
+        let fitnesses: Vec<f64> = self.population.iter().map(|net| net.get_fitness()).collect();
+        GenerationStats::from_fitnesses(self.generation, &fitnesses, self.params.objective)
     }
 }
 
@@ -0,0 +1,100 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+/// Scheme drawing the starting weights of a layer. Each neuron holds its
+/// bias followed by its input weights
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WeightInit {
+    /// (low, high)
+    /// Uniform from low..high, biases included
+    Uniform(f64, f64),
+    /// (mean, std_deviation)
+    /// Normally distributed, biases included
+    Gaussian(f64, f64),
+    /// Glorot uniform from -sqrt(6 / (fan_in + fan_out))..sqrt(6 / (fan_in + fan_out)), biases at 0.0
+    Xavier,
+    /// Normally distributed with std_deviation sqrt(2 / fan_in), biases at 0.0
+    He,
+    /// (gain)
+    /// Orthonormal rows, or columns for layers wider than their input,
+    /// scaled by gain, biases at 0.0
+    Orthogonal(f64),
+    /// (sparsity, std_deviation)
+    /// Leaves the given fraction of each neuron's input weights at 0.0,
+    /// the rest normally distributed, biases at 0.0
+    Sparse(f64, f64),
+    /// Every weight and bias at 0.0
+    Zeros,
+}
+
+impl Default for WeightInit {
+    fn default() -> Self {
+        Self::Uniform(-1.0, 1.0)
+    }
+}
+
+impl WeightInit {
+    /// Overwrites the weights of a layer, one row of bias and inputs per neuron
+    pub fn initialize<R: Rng>(&self, layer: &mut [Vec<f64>], rng: &mut R) {
+        let fan_out = layer.len();
+        let fan_in = layer.first().map_or(0, |n| n.len() - 1);
+        let gaussian = |rng: &mut R| rng.sample::<f64, _>(StandardNormal);
+
+        match *self {
+            WeightInit::Uniform(low, high) => layer.iter_mut().flatten().for_each(|w| *w = rng.gen_range(low..high)),
+            WeightInit::Gaussian(mean, std_deviation) => layer.iter_mut().flatten().for_each(|w| *w = mean + std_deviation * gaussian(rng)),
+            WeightInit::Xavier => {
+                let limit = (6.0 / (fan_in + fan_out) as f64).sqrt();
+                Self::with_zero_bias(layer, |_| rng.gen_range(-limit..limit));
+            },
+            WeightInit::He => {
+                let std_deviation = (2.0 / fan_in.max(1) as f64).sqrt();
+                Self::with_zero_bias(layer, |_| std_deviation * gaussian(rng));
+            },
+            WeightInit::Orthogonal(gain) => {
+                let matrix = Self::orthogonal(fan_out, fan_in, rng);
+                Self::with_zero_bias(layer, |(i, k)| gain * matrix[i][k]);
+            },
+            WeightInit::Sparse(sparsity, std_deviation) => {
+                Self::with_zero_bias(layer, |_| if rng.gen_range(0.0..1.0) < sparsity { 0.0 } else { std_deviation * gaussian(rng) });
+            },
+            WeightInit::Zeros => layer.iter_mut().flatten().for_each(|w| *w = 0.0),
+        }
+    }
+
+    /// Zeroes the biases and draws every input weight from (neuron, input)
+    fn with_zero_bias<F: FnMut((usize, usize)) -> f64>(layer: &mut [Vec<f64>], mut draw: F) {
+        for (i, neuron) in layer.iter_mut().enumerate() {
+            neuron[0] = 0.0;
+            for (k, w) in neuron.iter_mut().skip(1).enumerate() {
+                *w = draw((i, k));
+            }
+        }
+    }
+
+    /// Random rows x columns matrix whose shorter side is orthonormal,
+    /// Gram-Schmidt over a Gaussian matrix
+    fn orthogonal<R: Rng>(rows: usize, columns: usize, rng: &mut R) -> Vec<Vec<f64>> {
+        let (short, long) = (rows.min(columns), rows.max(columns));
+        let mut basis: Vec<Vec<f64>> = Vec::with_capacity(short);
+
+        while basis.len() < short {
+            let mut v: Vec<f64> = (0..long).map(|_| rng.sample::<f64, _>(StandardNormal)).collect();
+            for b in basis.iter() {
+                let projection = v.iter().zip(b.iter()).fold(0.0, |sum, (x, y)| sum + x * y);
+                v.iter_mut().zip(b.iter()).for_each(|(x, y)| *x -= projection * y);
+            }
+            let norm = v.iter().fold(0.0, |sum, x| sum + x * x).sqrt();
+            // Redraw the rare vector lying in the span of the basis
+            if norm > 1e-10 {
+                basis.push(v.into_iter().map(|x| x / norm).collect());
+            }
+        }
+
+        if rows <= columns {
+            basis
+        } else {
+            (0..rows).map(|i| (0..columns).map(|k| basis[k][i]).collect()).collect()
+        }
+    }
+}
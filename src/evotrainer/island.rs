@@ -0,0 +1,230 @@
+use rand::Rng;
+use crate::{evonet::EvoNet, genome::Genome};
+use super::{evotrainer::EvoTrainer, trainer_builder::TrainerBuildError};
+
+/// Which islands receive the migrants of an island
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MigrationTopology {
+    /// Island i sends to island i + 1, the last island to the first
+    #[default]
+    Ring,
+    /// Every island sends to every other island
+    FullyConnected,
+    /// Every island sends to one other island picked at random each migration
+    Random
+}
+
+/// Coordinates several independently configured trainers, the islands,
+/// moving the best members between them every few generations
+pub struct IslandModel<G: Genome = EvoNet> {
+    islands: Vec<EvoTrainer<G>>,
+    topology: MigrationTopology,
+    migration_interval: usize,
+    migration_count: usize,
+    parallel: bool,
+    generation: usize
+}
+
+impl <G: Genome> IslandModel<G> {
+    pub fn get_islands(&self) -> &[EvoTrainer<G>] {
+        &self.islands
+    }
+
+    pub fn get_island_mut(&mut self, index: usize) -> Option<&mut EvoTrainer<G>> {
+        self.islands.get_mut(index)
+    }
+
+    /// Best member over all islands
+    pub fn extract_best(&self) -> G {
+        let objective = self.islands[0].get_objective();
+        self.islands.iter()
+            .map(|island| island.extract_best())
+            .reduce(|best, net| if objective.is_better(net.get_fitness(), best.get_fitness()) { net } else { best })
+            .unwrap()
+    }
+
+    /// Trains every island, migrating each time the
+    /// total generation count reaches a multiple of the migration interval
+    pub fn train(&mut self, generations: usize) {
+        let mut remaining = generations;
+        while remaining > 0 {
+            let epoch = (self.migration_interval - self.generation % self.migration_interval).min(remaining);
+            self.train_islands(epoch);
+            self.generation += epoch;
+            remaining -= epoch;
+
+            if self.generation.is_multiple_of(self.migration_interval) {
+                self.migrate();
+            }
+        }
+    }
+
+    fn train_islands(&mut self, generations: usize) {
+        if self.parallel {
+            std::thread::scope(|scope| {
+                self.islands.iter_mut().for_each(|island| {
+                    scope.spawn(move || island.train(generations));
+                });
+            });
+        } else {
+            self.islands.iter_mut().for_each(|island| island.train(generations));
+        }
+    }
+
+    /// Sends clones of each island's best members to its neighbours
+    /// along the topology, replacing the neighbours' worst members
+    pub fn migrate(&mut self) {
+        let island_count = self.islands.len();
+        let emigrants: Vec<Vec<G>> = self.islands.iter().map(|island| island.extract_top(self.migration_count)).collect();
+        let mut immigrants: Vec<Vec<G>> = vec![Vec::new(); island_count];
+        let mut rng = rand::thread_rng();
+
+        for (source, nets) in emigrants.into_iter().enumerate() {
+            match self.topology {
+                MigrationTopology::Ring => immigrants[(source + 1) % island_count].extend(nets),
+                MigrationTopology::FullyConnected => {
+                    (0..island_count).filter(|target| *target != source).for_each(|target| {
+                        immigrants[target].extend(nets.iter().cloned());
+                    });
+                },
+                MigrationTopology::Random => {
+                    let mut target = rng.gen_range(0..island_count - 1);
+                    if target >= source {
+                        target += 1;
+                    }
+                    immigrants[target].extend(nets);
+                }
+            }
+        }
+
+        self.islands.iter_mut().zip(immigrants).for_each(|(island, nets)| island.insert_members(nets));
+    }
+}
+
+pub struct IslandBuilder<G: Genome = EvoNet> {
+    islands: Vec<EvoTrainer<G>>,
+    topology: MigrationTopology,
+    migration_interval: Option<usize>,
+    migration_count: Option<usize>,
+    parallel: bool
+}
+
+impl <G: Genome> Default for IslandBuilder<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <G: Genome> IslandBuilder<G> {
+
+    pub fn new() -> Self {
+        Self {
+            islands: Vec::new(),
+            topology: MigrationTopology::Ring,
+            migration_interval: None,
+            migration_count: None,
+            parallel: false
+        }
+    }
+
+    pub fn build(self) -> Result<IslandModel<G>, TrainerBuildError> {
+        let interval = self.migration_interval.ok_or(TrainerBuildError::VariableNotSet(String::from("migration_interval not set")))?;
+        let count = self.migration_count.unwrap_or(1);
+
+        if self.islands.len() < 2 {
+            return Err(TrainerBuildError::ValidationError(String::from("island model needs at least 2 islands")));
+        }
+
+        if interval == 0 {
+            return Err(TrainerBuildError::ValidationError(String::from("migration_interval must be greater than 0")));
+        }
+
+        let objective = self.islands[0].get_objective();
+        let reference = self.islands[0].extract_best();
+        for island in self.islands.iter() {
+            if island.get_objective() != objective {
+                return Err(TrainerBuildError::ValidationError(String::from("islands must share the same objective")));
+            }
+            if !island.extract_best().crossover_compatible(&reference) {
+                return Err(TrainerBuildError::ValidationError(String::from("islands must hold genomes that can meet in crossover")));
+            }
+            if count >= island.get_population_size() {
+                return Err(TrainerBuildError::ValidationError(String::from("migration_count must be smaller than every island's population_size")));
+            }
+        }
+
+        Ok(IslandModel {
+            islands: self.islands,
+            topology: self.topology,
+            migration_interval: interval,
+            migration_count: count,
+            parallel: self.parallel,
+            generation: 0
+        })
+    }
+
+    /// Adds a trainer as an island, each island keeps its own settings
+    pub fn add_island(&mut self, island: EvoTrainer<G>) {
+        self.islands.push(island);
+    }
+
+    pub fn set_topology(&mut self, topology: MigrationTopology) {
+        self.topology = topology;
+    }
+
+    /// Generations between migrations
+    pub fn set_migration_interval(&mut self, generations: usize) {
+        self.migration_interval = Some(generations);
+    }
+
+    /// Members each island sends per migration. Defaults to 1
+    pub fn set_migration_count(&mut self, count: usize) {
+        self.migration_count = Some(count);
+    }
+
+    /// Trains the islands on separate threads between migrations
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IslandBuilder;
+    use crate::{evonet::{EvoNet, EvoNetConfig}, evotrainer::{evotrainer::EvoTrainer, trainer_builder::TrainerBuilder}, genome::Genome, recurrent::RecurrentNet};
+
+    fn zero_fitness<G: Genome>(net: &mut G) -> f64 {
+        net.calc(&[0.0, 0.0]);
+        0.0
+    }
+
+    fn island<G: Genome>(architecture: &[usize], config: G::Config) -> EvoTrainer<G> {
+        let mut builder = TrainerBuilder::<G>::new();
+        builder.set_architecture(architecture);
+        builder.set_population_size(4);
+        builder.set_fitness_function(zero_fitness::<G>);
+        builder.set_genome_config(config);
+        builder.build().unwrap()
+    }
+
+    fn islands<G: Genome>(first: &[usize], second: &[usize], config: G::Config) -> IslandBuilder<G> {
+        let mut builder = IslandBuilder::<G>::new();
+        builder.add_island(island(first, config.clone()));
+        builder.add_island(island(second, config));
+        builder.set_migration_interval(1);
+        builder
+    }
+
+    #[test]
+    fn fixed_shape_islands_must_share_the_architecture() {
+        assert!(islands::<RecurrentNet>(&[2, 3, 1], &[2, 3, 1], Default::default()).build().is_ok());
+        assert!(islands::<RecurrentNet>(&[2, 3, 1], &[2, 5, 1], Default::default()).build().is_err());
+    }
+
+    #[test]
+    fn evonet_islands_may_differ_in_hidden_shape() {
+        let config = EvoNetConfig { add_neuron_rate: 0.1, ..Default::default() };
+        assert!(islands::<EvoNet>(&[2, 3, 1], &[2, 5, 4, 1], config).build().is_ok());
+        assert!(islands::<EvoNet>(&[2, 3, 1], &[2, 3, 2], config).build().is_err());
+    }
+}
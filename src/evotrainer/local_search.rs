@@ -0,0 +1,284 @@
+use rand::Rng;
+use crate::evonet::EvoNet;
+use super::{
+    evotrainer::HasFitness,
+    mutation::MutationOperator,
+    objective::Objective,
+    statistics::GenerationStats,
+    trainer_builder::TrainerBuildError
+};
+
+/// Temperature of simulated annealing after k iterations
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoolingSchedule {
+    /// T0 * alpha^k (alpha)
+    Exponential(f64),
+    /// T0 - decrement * k, bottoming out just above zero (decrement)
+    Linear(f64),
+    /// T0 / ln(k + 2)
+    Logarithmic,
+}
+
+impl Default for CoolingSchedule {
+    fn default() -> Self {
+        CoolingSchedule::Exponential(0.99)
+    }
+}
+
+impl CoolingSchedule {
+    pub fn temperature(&self, initial: f64, iteration: usize) -> f64 {
+        let k = iteration as f64;
+        match *self {
+            CoolingSchedule::Exponential(alpha) => initial * alpha.powf(k),
+            CoolingSchedule::Linear(decrement) => (initial - decrement * k).max(f64::MIN_POSITIVE),
+            CoolingSchedule::Logarithmic => initial / (k + 2.0).ln(),
+        }
+    }
+}
+
+/// Acceptance rule of the single-solution search
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LocalSearchMethod {
+    /// (1+1)-ES perturbing every weight with a Gaussian step
+    /// adapted by the 1/5th success rule (initial step size)
+    OnePlusOne(f64),
+    /// Accepts improvements only, restarting from a random net
+    /// after this many iterations without one (patience)
+    HillClimbing(usize),
+    /// Accepts worse nets with probability exp(-loss / T) (initial temperature, cooling)
+    SimulatedAnnealing(f64, CoolingSchedule),
+}
+
+impl Default for LocalSearchMethod {
+    fn default() -> Self {
+        LocalSearchMethod::OnePlusOne(0.1)
+    }
+}
+
+/// Single-solution baseline perturbing one net at a time
+pub struct LocalSearchTrainer {
+    current: EvoNet,
+    best: EvoNet,
+    architecture: Vec<usize>,
+    fitness_fn: fn(&mut EvoNet) -> f64,
+    objective: Objective,
+    method: LocalSearchMethod,
+    mutation_rate: f64,
+    mutation_operator: MutationOperator,
+    step_size: f64,
+    stale: usize,
+    restarts: usize,
+    iteration: usize,
+    statistics: Vec<GenerationStats>
+}
+
+impl LocalSearchTrainer {
+    /// One entry per iteration, best_fitness is the best so far and
+    /// mean_fitness the fitness of the current net
+    pub fn get_statistics(&self) -> &[GenerationStats] {
+        &self.statistics
+    }
+
+    /// Gaussian step of the (1+1)-ES
+    pub fn get_step_size(&self) -> f64 {
+        self.step_size
+    }
+
+    /// Temperature of simulated annealing, 0.0 for the other methods
+    pub fn get_temperature(&self) -> f64 {
+        match self.method {
+            LocalSearchMethod::SimulatedAnnealing(initial, cooling) => cooling.temperature(initial, self.iteration),
+            _ => 0.0,
+        }
+    }
+
+    pub fn get_restarts(&self) -> usize {
+        self.restarts
+    }
+
+    pub fn extract_best(&self) -> EvoNet {
+        self.best.clone()
+    }
+
+    pub fn train(&mut self, iterations: usize) {
+        let mut rng = rand::thread_rng();
+        if self.iteration == 0 {
+            self.evaluate_current();
+        }
+
+        (0..iterations).for_each(|_| {
+            let mut candidate = self.current.clone();
+            match self.method {
+                LocalSearchMethod::OnePlusOne(_) => candidate.mutate_with(1.0, MutationOperator::Gaussian(self.step_size)),
+                _ => candidate.mutate_with(self.mutation_rate, self.mutation_operator),
+            }
+            let fitness = (self.fitness_fn)(&mut candidate);
+            candidate.set_fitness(fitness);
+            let current_fitness = self.current.get_fitness();
+            let improved = self.objective.is_better(fitness, current_fitness);
+
+            let accept = match self.method {
+                LocalSearchMethod::OnePlusOne(_) => {
+                    // Success rate above 1/5 grows the step, below shrinks it
+                    self.step_size *= if improved { 1.5 } else { 1.5f64.powf(-0.25) };
+                    !self.objective.is_better(current_fitness, fitness)
+                },
+                LocalSearchMethod::HillClimbing(_) => improved,
+                LocalSearchMethod::SimulatedAnnealing(initial, cooling) => {
+                    let loss = self.objective.orient(current_fitness) - self.objective.orient(fitness);
+                    let temperature = cooling.temperature(initial, self.iteration);
+                    loss <= 0.0 || rng.gen_range(0.0..1.0) < (-loss / temperature).exp()
+                },
+            };
+
+            if accept {
+                self.current = candidate;
+            }
+            if self.objective.is_better(self.current.get_fitness(), self.best.get_fitness()) {
+                self.best = self.current.clone();
+            }
+
+            self.stale = if improved { 0 } else { self.stale + 1 };
+            if let LocalSearchMethod::HillClimbing(patience) = self.method {
+                if self.stale >= patience {
+                    self.restarts += 1;
+                    self.stale = 0;
+                    self.current = EvoNet::new(&self.architecture);
+                    self.evaluate_current();
+                }
+            }
+
+            self.statistics.push(GenerationStats {
+                generation: self.iteration,
+                best_fitness: self.best.get_fitness(),
+                mean_fitness: self.current.get_fitness(),
+                ..Default::default()
+            });
+            self.iteration += 1;
+        });
+    }
+
+    fn evaluate_current(&mut self) {
+        let fitness = (self.fitness_fn)(&mut self.current);
+        self.current.set_fitness(fitness);
+        if self.iteration == 0 || self.objective.is_better(fitness, self.best.get_fitness()) {
+            self.best = self.current.clone();
+        }
+    }
+}
+
+pub struct LocalSearchBuilder<'a> {
+    architecture: Option<&'a [usize]>,
+    fitness_function: Option<fn(&mut EvoNet) -> f64>,
+    objective: Objective,
+    method: LocalSearchMethod,
+    mutation_rate: f64,
+    mutation_operator: MutationOperator,
+}
+
+impl Default for LocalSearchBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a> LocalSearchBuilder<'a> {
+
+    pub fn new() -> Self {
+        Self {
+            architecture: None,
+            fitness_function: None,
+            objective: Objective::Maximize,
+            method: LocalSearchMethod::default(),
+            mutation_rate: 0.1,
+            mutation_operator: MutationOperator::default(),
+        }
+    }
+
+    pub fn build(&self) -> Result<LocalSearchTrainer, TrainerBuildError> {
+        let arch = self.architecture.ok_or(TrainerBuildError::VariableNotSet(String::from("architecture not set")))?;
+        let ft_fn = self.fitness_function.ok_or(TrainerBuildError::VariableNotSet(String::from("fitness_function not set")))?;
+
+        if arch.contains(&0) {
+            return Err(TrainerBuildError::ValidationError(String::from("architecure cannot contain 0's")));
+        }
+
+        if !(0.0..=1.0).contains(&self.mutation_rate) {
+            return Err(TrainerBuildError::ValidationError(String::from("mutation_rate must be between 0.0..=1.0")));
+        }
+
+        let step_size = match self.method {
+            LocalSearchMethod::OnePlusOne(step_size) => {
+                if step_size <= 0.0 {
+                    return Err(TrainerBuildError::ValidationError(String::from("initial step size must be greater than 0.0")));
+                }
+                step_size
+            },
+            LocalSearchMethod::HillClimbing(patience) => {
+                if patience == 0 {
+                    return Err(TrainerBuildError::ValidationError(String::from("hill climbing patience must be greater than 0")));
+                }
+                0.0
+            },
+            LocalSearchMethod::SimulatedAnnealing(temperature, cooling) => {
+                if temperature <= 0.0 {
+                    return Err(TrainerBuildError::ValidationError(String::from("initial temperature must be greater than 0.0")));
+                }
+                let valid_cooling = match cooling {
+                    CoolingSchedule::Exponential(alpha) => alpha > 0.0 && alpha < 1.0,
+                    CoolingSchedule::Linear(decrement) => decrement > 0.0,
+                    CoolingSchedule::Logarithmic => true,
+                };
+                if !valid_cooling {
+                    return Err(TrainerBuildError::ValidationError(String::from("cooling needs an alpha between 0.0..1.0 or a positive decrement")));
+                }
+                0.0
+            },
+        };
+
+        let net = EvoNet::new(arch);
+        Ok(LocalSearchTrainer {
+            best: net.clone(),
+            current: net,
+            architecture: arch.to_vec(),
+            fitness_fn: ft_fn,
+            objective: self.objective,
+            method: self.method,
+            mutation_rate: self.mutation_rate,
+            mutation_operator: self.mutation_operator,
+            step_size,
+            stale: 0,
+            restarts: 0,
+            iteration: 0,
+            statistics: Vec::new(),
+        })
+    }
+
+    pub fn set_architecture(&mut self, architecture: &'a[usize]) {
+        self.architecture = Some(architecture);
+    }
+
+    pub fn set_fitness_function(&mut self, fit_fn: fn(&mut EvoNet) -> f64) {
+        self.fitness_function = Some(fit_fn);
+    }
+
+    /// Defaults to `Objective::Maximize`
+    pub fn set_objective(&mut self, objective: Objective) {
+        self.objective = objective;
+    }
+
+    /// Defaults to `LocalSearchMethod::OnePlusOne(0.1)`
+    pub fn set_method(&mut self, method: LocalSearchMethod) {
+        self.method = method;
+    }
+
+    /// Chance each weight is perturbed by hill climbing and annealing. Defaults to 0.1
+    pub fn set_mutation_rate(&mut self, rate: f64) {
+        self.mutation_rate = rate;
+    }
+
+    /// Perturbation of hill climbing and annealing. Defaults to `MutationOperator::Gaussian(0.1)`
+    pub fn set_mutation_operator(&mut self, operator: MutationOperator) {
+        self.mutation_operator = operator;
+    }
+}
@@ -0,0 +1,336 @@
+use std::{collections::HashMap, io::Write};
+use rand::{seq::IteratorRandom, Rng};
+use crate::evonet::EvoNet;
+use super::{
+    crossover::CrossoverOperator,
+    evotrainer::HasFitness,
+    mutation::MutationOperator,
+    objective::Objective,
+    trainer_builder::TrainerBuildError
+};
+
+/// One axis of the archive grid, behaviour values outside
+/// the range fall into the first or last bin
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FeatureDimension {
+    pub min: f64,
+    pub max: f64,
+    pub bins: usize,
+}
+
+impl FeatureDimension {
+    fn bin(&self, value: f64) -> usize {
+        let position = (value - self.min) / (self.max - self.min);
+        ((position * self.bins as f64).floor().max(0.0) as usize).min(self.bins - 1)
+    }
+}
+
+/// Best net found for a cell of the archive
+#[derive(Clone)]
+pub struct Elite {
+    pub net: EvoNet,
+    pub descriptor: Vec<f64>,
+}
+
+/// Summary of one trained MAP-Elites generation
+#[derive(Clone, Debug, Default)]
+pub struct MapElitesStats {
+    pub generation: usize,
+    /// Filled cells
+    pub filled_cells: usize,
+    /// Share of the cells that are filled
+    pub coverage: f64,
+    /// Sum over the elites of their oriented fitness above the QD offset
+    pub qd_score: f64,
+    /// Raw fitness of the best elite
+    pub best_fitness: f64,
+}
+
+/// Quality-diversity driver keeping the best net for every cell
+/// of a grid over user defined behaviour descriptors
+pub struct MapElites {
+    archive: HashMap<Vec<usize>, Elite>,
+    dimensions: Vec<FeatureDimension>,
+    architecture: Vec<usize>,
+    fitness_fn: fn(&mut EvoNet) -> f64,
+    descriptor_fn: fn(&mut EvoNet) -> Vec<f64>,
+    objective: Objective,
+    initial_batch: usize,
+    batch_size: usize,
+    crossover_rate: f64,
+    mutation_rate: f64,
+    crossover_operator: CrossoverOperator,
+    mutation_operator: MutationOperator,
+    qd_offset: f64,
+    generation: usize,
+    statistics: Vec<MapElitesStats>
+}
+
+impl MapElites {
+    pub fn get_statistics(&self) -> &[MapElitesStats] {
+        &self.statistics
+    }
+
+    /// Elites keyed by the bin index of each dimension
+    pub fn get_archive(&self) -> &HashMap<Vec<usize>, Elite> {
+        &self.archive
+    }
+
+    pub fn extract_best(&self) -> Option<EvoNet> {
+        self.archive.values()
+            .map(|elite| &elite.net)
+            .reduce(|best, net| if self.objective.is_better(net.get_fitness(), best.get_fitness()) { net } else { best })
+            .cloned()
+    }
+
+    /// Fills the archive with random nets on the first generation, afterwards
+    /// each generation evaluates a batch of offspring of random elites
+    pub fn train(&mut self, generations: usize) {
+        let mut rng = rand::thread_rng();
+        (0..generations).for_each(|_| {
+            if self.archive.is_empty() {
+                for _ in 0..self.initial_batch {
+                    self.insert(EvoNet::new(&self.architecture));
+                }
+            } else {
+                let offspring: Vec<EvoNet> = (0..self.batch_size).map(|_| {
+                    let parent = &self.archive.values().choose(&mut rng).unwrap().net;
+                    let mut child = if rng.gen_range(0.0..1.0) < self.crossover_rate {
+                        let other = &self.archive.values().choose(&mut rng).unwrap().net;
+                        let fitnesses = [self.objective.orient(parent.get_fitness()), self.objective.orient(other.get_fitness())];
+                        EvoNet::from_many_parents(&[parent, other], &fitnesses, self.crossover_operator)
+                    } else {
+                        parent.clone()
+                    };
+                    child.mutate_with(self.mutation_rate, self.mutation_operator);
+                    child
+                }).collect();
+
+                for child in offspring {
+                    self.insert(child);
+                }
+            }
+
+            self.statistics.push(self.summarize());
+            self.generation += 1;
+        });
+    }
+
+    /// Evaluates the net and stores it if its cell is empty or it beats the occupant.
+    /// Returns true if the net was stored. Panics if the descriptor
+    /// doesn't hold one value per feature dimension
+    pub fn insert(&mut self, mut net: EvoNet) -> bool {
+        let fitness = (self.fitness_fn)(&mut net);
+        net.set_fitness(fitness);
+        let descriptor = (self.descriptor_fn)(&mut net);
+        assert_eq!(descriptor.len(), self.dimensions.len(), "descriptor_function must return one value per feature dimension");
+        let cell: Vec<usize> = self.dimensions.iter().zip(descriptor.iter()).map(|(d, v)| d.bin(*v)).collect();
+
+        let replace = match self.archive.get(&cell) {
+            Some(elite) => self.objective.is_better(fitness, elite.net.get_fitness()),
+            None => true,
+        };
+
+        if replace {
+            self.archive.insert(cell, Elite { net, descriptor });
+        }
+        replace
+    }
+
+    /// Writes the archive as CSV ordered by cell, one elite per row with its
+    /// cell, descriptor, raw fitness, architecture and flattened weights.
+    /// Architecture and weights are space separated
+    pub fn export_archive<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let cells: Vec<String> = (0..self.dimensions.len()).map(|i| format!("cell_{}", i)).collect();
+        let descriptors: Vec<String> = (0..self.dimensions.len()).map(|i| format!("descriptor_{}", i)).collect();
+        writeln!(writer, "{},{},fitness,architecture,weights", cells.join(","), descriptors.join(","))?;
+
+        let mut keys: Vec<&Vec<usize>> = self.archive.keys().collect();
+        keys.sort();
+        for key in keys {
+            let elite = &self.archive[key];
+            let cell: Vec<String> = key.iter().map(|c| c.to_string()).collect();
+            let descriptor: Vec<String> = elite.descriptor.iter().map(|d| d.to_string()).collect();
+            let architecture: Vec<String> = elite.net.get_architecture().iter().map(|n| n.to_string()).collect();
+            let weights: Vec<String> = elite.net.get_weights().iter().map(|w| w.to_string()).collect();
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                cell.join(","),
+                descriptor.join(","),
+                elite.net.get_fitness(),
+                architecture.join(" "),
+                weights.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+
+    fn summarize(&self) -> MapElitesStats {
+        let cells = self.dimensions.iter().fold(1, |cells, d| cells * d.bins);
+        let offset = self.objective.orient(self.qd_offset);
+
+        MapElitesStats {
+            generation: self.generation,
+            filled_cells: self.archive.len(),
+            coverage: self.archive.len() as f64 / cells as f64,
+            qd_score: self.archive.values().fold(0.0, |sum, elite| sum + self.objective.orient(elite.net.get_fitness()) - offset),
+            best_fitness: self.extract_best().map(|net| net.get_fitness()).unwrap_or(f64::NAN),
+        }
+    }
+}
+
+pub struct MapElitesBuilder<'a> {
+    architecture: Option<&'a [usize]>,
+    dimensions: Vec<FeatureDimension>,
+    fitness_function: Option<fn(&mut EvoNet) -> f64>,
+    descriptor_function: Option<fn(&mut EvoNet) -> Vec<f64>>,
+    objective: Objective,
+    initial_batch: Option<usize>,
+    batch_size: Option<usize>,
+    crossover_rate: Option<f64>,
+    mutation_rate: Option<f64>,
+    crossover_operator: CrossoverOperator,
+    mutation_operator: MutationOperator,
+    qd_offset: f64,
+}
+
+impl Default for MapElitesBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a> MapElitesBuilder<'a> {
+
+    pub fn new() -> Self {
+        Self {
+            architecture: None,
+            dimensions: Vec::new(),
+            fitness_function: None,
+            descriptor_function: None,
+            objective: Objective::Maximize,
+            initial_batch: None,
+            batch_size: None,
+            crossover_rate: None,
+            mutation_rate: None,
+            crossover_operator: CrossoverOperator::Uniform,
+            mutation_operator: MutationOperator::default(),
+            qd_offset: 0.0,
+        }
+    }
+
+    pub fn build(&self) -> Result<MapElites, TrainerBuildError> {
+        let arch = self.architecture.ok_or(TrainerBuildError::VariableNotSet(String::from("architecture not set")))?;
+        let ft_fn = self.fitness_function.ok_or(TrainerBuildError::VariableNotSet(String::from("fitness_function not set")))?;
+        let desc_fn = self.descriptor_function.ok_or(TrainerBuildError::VariableNotSet(String::from("descriptor_function not set")))?;
+        let batch = self.batch_size.ok_or(TrainerBuildError::VariableNotSet(String::from("batch_size not set")))?;
+        let initial = self.initial_batch.unwrap_or(batch);
+        let cross_rate = self.crossover_rate.unwrap_or(0.0);
+        let mut_rate = self.mutation_rate.unwrap_or(0.1);
+
+        if arch.contains(&0) {
+            return Err(TrainerBuildError::ValidationError(String::from("architecure cannot contain 0's")));
+        }
+
+        if self.dimensions.is_empty() {
+            return Err(TrainerBuildError::ValidationError(String::from("MAP-Elites needs at least one feature dimension")));
+        }
+
+        for dimension in self.dimensions.iter() {
+            if dimension.bins == 0 || dimension.max <= dimension.min {
+                return Err(TrainerBuildError::ValidationError(String::from("feature dimensions need bins and a max greater than min")));
+            }
+        }
+
+        if batch == 0 || initial == 0 {
+            return Err(TrainerBuildError::ValidationError(String::from("batch_size and initial_batch must be greater than 0")));
+        }
+
+        if !(0.0..=1.0).contains(&cross_rate) {
+            return Err(TrainerBuildError::ValidationError(String::from("crossover_rate must be between 0.0..=1.0")));
+        }
+
+        if !(0.0..=1.0).contains(&mut_rate) {
+            return Err(TrainerBuildError::ValidationError(String::from("mutation_rate must be between 0.0..=1.0")));
+        }
+
+        Ok(MapElites {
+            archive: HashMap::new(),
+            dimensions: self.dimensions.clone(),
+            architecture: arch.to_vec(),
+            fitness_fn: ft_fn,
+            descriptor_fn: desc_fn,
+            objective: self.objective,
+            initial_batch: initial,
+            batch_size: batch,
+            crossover_rate: cross_rate,
+            mutation_rate: mut_rate,
+            crossover_operator: self.crossover_operator,
+            mutation_operator: self.mutation_operator,
+            qd_offset: self.qd_offset,
+            generation: 0,
+            statistics: Vec::new(),
+        })
+    }
+
+    pub fn set_architecture(&mut self, architecture: &'a[usize]) {
+        self.architecture = Some(architecture);
+    }
+
+    /// Adds a grid axis, matching the next value returned by the descriptor function
+    pub fn add_feature_dimension(&mut self, dimension: FeatureDimension) {
+        self.dimensions.push(dimension);
+    }
+
+    pub fn set_fitness_function(&mut self, fit_fn: fn(&mut EvoNet) -> f64) {
+        self.fitness_function = Some(fit_fn);
+    }
+
+    /// Maps a net to one behaviour value per feature dimension
+    pub fn set_descriptor_function(&mut self, desc_fn: fn(&mut EvoNet) -> Vec<f64>) {
+        self.descriptor_function = Some(desc_fn);
+    }
+
+    /// Defaults to `Objective::Maximize`
+    pub fn set_objective(&mut self, objective: Objective) {
+        self.objective = objective;
+    }
+
+    /// Random nets evaluated to seed the archive. Defaults to batch_size
+    pub fn set_initial_batch(&mut self, size: usize) {
+        self.initial_batch = Some(size);
+    }
+
+    /// Offspring evaluated each generation
+    pub fn set_batch_size(&mut self, size: usize) {
+        self.batch_size = Some(size);
+    }
+
+    /// Chance an offspring is recombined from two elites
+    /// instead of cloned from one. Defaults to 0.0
+    pub fn set_crossover_rate(&mut self, rate: f64) {
+        self.crossover_rate = Some(rate);
+    }
+
+    /// Defaults to 0.1
+    pub fn set_mutation_rate(&mut self, rate: f64) {
+        self.mutation_rate = Some(rate);
+    }
+
+    /// Defaults to `CrossoverOperator::Uniform`
+    pub fn set_crossover_operator(&mut self, operator: CrossoverOperator) {
+        self.crossover_operator = operator;
+    }
+
+    /// Defaults to `MutationOperator::Gaussian(0.1)`
+    pub fn set_mutation_operator(&mut self, operator: MutationOperator) {
+        self.mutation_operator = operator;
+    }
+
+    /// Raw fitness counted as zero quality in the QD score,
+    /// should be no better than any reachable fitness. Defaults to 0.0
+    pub fn set_qd_offset(&mut self, offset: f64) {
+        self.qd_offset = offset;
+    }
+}
@@ -0,0 +1,191 @@
+use crate::genome::Genome;
+use super::{crossover::CrossoverFamily, speciation::DistanceMetric};
+
+/// How many times a rejected family is redrawn from the selection
+/// strategy before the last draw is accepted regardless
+pub const MAX_MATING_ATTEMPTS: usize = 10;
+
+/// Restriction applied on top of any `ParentSelectionStrategy`
+/// deciding which of the drawn parent pairs may produce offspring
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MatingPolicy {
+    /// Every family drawn by the selection strategy is accepted
+    #[default]
+    Unrestricted,
+    /// Redraws families where an individual appears more than once
+    ForbidSelfMating,
+    /// (candidates)
+    /// Draws the given amount of candidate families and keeps the one whose
+    /// parents are furthest apart by mean pairwise genome distance
+    PreferDissimilar(usize),
+    /// (candidates)
+    /// Draws the given amount of candidate families and keeps the one whose
+    /// parents are closest by mean pairwise genome distance.
+    /// Self-mating is never preferred
+    PreferSimilar(usize),
+    /// (generations)
+    /// Redraws families where any two parents are the same individual or
+    /// share an ancestor within the given amount of generations
+    IncestPrevention(usize),
+}
+
+/// Ancestry of a population member, used for incest prevention
+#[derive(Clone, Debug, Default)]
+pub struct Lineage {
+    pub id: usize,
+    /// `ancestors[g]` holds the ids of the ancestors `g + 1` generations back
+    pub ancestors: Vec<Vec<usize>>,
+}
+
+impl Lineage {
+    pub fn new(id: usize) -> Self {
+        Self { id, ancestors: Vec::new() }
+    }
+
+    /// Lineage of an offspring of the given parents,
+    /// remembering at most `depth` generations of ancestors
+    pub fn from_parents(id: usize, parents: &[&Lineage], depth: usize) -> Self {
+        let mut ancestors: Vec<Vec<usize>> = Vec::with_capacity(depth);
+        for g in 0..depth {
+            let mut level: Vec<usize> = Vec::new();
+            for parent in parents.iter() {
+                let ids = if g == 0 {
+                    std::slice::from_ref(&parent.id)
+                } else {
+                    match parent.ancestors.get(g - 1) {
+                        Some(ids) => ids.as_slice(),
+                        None => &[],
+                    }
+                };
+                ids.iter().for_each(|id| {
+                    if !level.contains(id) {
+                        level.push(*id);
+                    }
+                });
+            }
+
+            if level.is_empty() {
+                break;
+            }
+            ancestors.push(level);
+        }
+
+        Self { id, ancestors }
+    }
+
+    /// True if both lineages are the same individual
+    /// or share an ancestor within `depth` generations
+    pub fn is_related(&self, other: &Lineage, depth: usize) -> bool {
+        if self.id == other.id {
+            return true;
+        }
+
+        let known = |lineage: &Lineage| -> Vec<usize> {
+            let mut ids = vec![lineage.id];
+            lineage.ancestors.iter().take(depth).for_each(|level| ids.extend_from_slice(level));
+            ids
+        };
+
+        let other_known = known(other);
+        known(self).iter().any(|id| other_known.contains(id))
+    }
+}
+
+impl MatingPolicy {
+    /// Generations of ancestry the trainer has to track for this policy
+    pub fn lineage_depth(&self) -> usize {
+        match self {
+            MatingPolicy::IncestPrevention(generations) => *generations,
+            _ => 0,
+        }
+    }
+
+    /// Checks the family drawn by the selection strategy and redraws it
+    /// with `redraw` until it satisfies the policy
+    pub fn restrict<G: Genome, F: FnMut() -> CrossoverFamily>(
+        &self,
+        family: CrossoverFamily,
+        mut redraw: F,
+        population: &[G],
+        lineage: &[Lineage]
+    ) -> CrossoverFamily {
+        match *self {
+            MatingPolicy::Unrestricted => family,
+            MatingPolicy::ForbidSelfMating => {
+                let mut family = family;
+                for _ in 0..MAX_MATING_ATTEMPTS {
+                    if !Self::any_pair(&family, |a, b| a == b) {
+                        break;
+                    }
+                    family = redraw();
+                }
+                family
+            },
+            MatingPolicy::IncestPrevention(generations) => {
+                let mut family = family;
+                for _ in 0..MAX_MATING_ATTEMPTS {
+                    if !Self::any_pair(&family, |a, b| lineage[a].is_related(&lineage[b], generations)) {
+                        break;
+                    }
+                    family = redraw();
+                }
+                family
+            },
+            MatingPolicy::PreferDissimilar(candidates) | MatingPolicy::PreferSimilar(candidates) => {
+                let dissimilar = matches!(self, MatingPolicy::PreferDissimilar(_));
+                let is_selfing = |family: &CrossoverFamily| Self::any_pair(family, |a, b| a == b);
+
+                let mut best = family;
+                let mut best_distance = Self::mean_distance(&best, population);
+                for _ in 1..candidates {
+                    let candidate = redraw();
+                    let candidate_distance = Self::mean_distance(&candidate, population);
+                    let better = if is_selfing(&best) {
+                        !is_selfing(&candidate)
+                    } else if is_selfing(&candidate) {
+                        false
+                    } else if dissimilar {
+                        candidate_distance > best_distance
+                    } else {
+                        candidate_distance < best_distance
+                    };
+
+                    if better {
+                        best = candidate;
+                        best_distance = candidate_distance;
+                    }
+                }
+                best
+            }
+        }
+    }
+
+    /// True if `check` holds for any two parents of the family
+    fn any_pair<F: Fn(usize, usize) -> bool>(family: &CrossoverFamily, check: F) -> bool {
+        for (i, a) in family.parents.iter().enumerate() {
+            for b in family.parents[i + 1..].iter() {
+                if check(a.index, b.index) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn mean_distance<G: Genome>(family: &CrossoverFamily, population: &[G]) -> f64 {
+        let mut sum = 0.0;
+        let mut pairs = 0;
+        for (i, a) in family.parents.iter().enumerate() {
+            for b in family.parents[i + 1..].iter() {
+                sum += population[a.index].distance_with(&population[b.index], DistanceMetric::Euclidean);
+                pairs += 1;
+            }
+        }
+
+        if pairs == 0 {
+            0.0
+        } else {
+            sum / pairs as f64
+        }
+    }
+}
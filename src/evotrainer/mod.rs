@@ -1,3 +1,21 @@
 pub mod trainer_builder;
+#[allow(clippy::module_inception)]
 pub mod evotrainer;
-pub mod crossover;
\ No newline at end of file
+pub mod crossover;
+pub mod scaling;
+pub mod objective;
+pub mod mating;
+pub mod mutation;
+pub mod adaptive;
+pub mod statistics;
+pub mod speciation;
+pub mod island;
+pub mod nsga;
+pub mod novelty;
+pub mod map_elites;
+pub mod cmaes;
+pub mod nes;
+pub mod differential;
+pub mod swarm;
+pub mod local_search;
+pub mod initialization;
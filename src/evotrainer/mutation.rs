@@ -0,0 +1,42 @@
+use rand::{thread_rng, Rng};
+use rand_distr::StandardNormal;
+
+/// Perturbation applied to each weight picked by the mutation rate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MutationOperator {
+    /// (std_deviation)
+    /// Adds normally distributed noise to the weight
+    Gaussian(f64),
+    /// (range)
+    /// Adds uniform noise from -range..=range to the weight
+    Uniform(f64),
+    /// Replaces the weight with a fresh uniform value from -1.0..=1.0
+    Reset
+}
+
+impl Default for MutationOperator {
+    fn default() -> Self {
+        Self::Gaussian(0.1)
+    }
+}
+
+impl MutationOperator {
+    /// Perturbed value of a single weight
+    pub fn perturb<R: Rng>(&self, weight: f64, rng: &mut R) -> f64 {
+        match *self {
+            MutationOperator::Gaussian(std_deviation) => weight + std_deviation * rng.sample::<f64, _>(StandardNormal),
+            MutationOperator::Uniform(range) => weight + rng.gen_range(-range..=range),
+            MutationOperator::Reset => rng.gen_range(-1.0..=1.0),
+        }
+    }
+
+    /// Perturbs each weight with probability `frequency`
+    pub fn mutate(&self, weights: &mut [f64], frequency: f64) {
+        let mut rng = thread_rng();
+        weights.iter_mut().for_each(|w| {
+            if rng.gen_range(0.0..=1.0) <= frequency {
+                *w = self.perturb(*w, &mut rng);
+            }
+        });
+    }
+}
@@ -0,0 +1,268 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+use crate::evonet::EvoNet;
+use super::{
+    evotrainer::HasFitness,
+    objective::Objective,
+    statistics::GenerationStats,
+    trainer_builder::TrainerBuildError
+};
+
+/// Rule applying the estimated gradient to the centre
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientOptimizer {
+    /// (momentum)
+    Sgd(f64),
+    /// (beta1, beta2)
+    Adam(f64, f64),
+}
+
+impl Default for GradientOptimizer {
+    fn default() -> Self {
+        GradientOptimizer::Adam(0.9, 0.999)
+    }
+}
+
+/// Natural evolution strategy estimating a gradient of the fitness from
+/// antithetic Gaussian perturbations of a central net
+pub struct NesTrainer {
+    center: EvoNet,
+    fitness_fn: fn(&mut EvoNet) -> f64,
+    objective: Objective,
+    pairs: usize,
+    noise_std: f64,
+    learning_rate: f64,
+    optimizer: GradientOptimizer,
+    weight_decay: f64,
+    first_moment: Vec<f64>,
+    second_moment: Vec<f64>,
+    best: Option<EvoNet>,
+    generation: usize,
+    statistics: Vec<GenerationStats>
+}
+
+impl NesTrainer {
+    pub fn get_statistics(&self) -> &[GenerationStats] {
+        &self.statistics
+    }
+
+    /// Current centre of the search distribution, evaluated with the fitness function
+    pub fn extract_center(&self) -> EvoNet {
+        let mut net = self.center.clone();
+        let fitness = (self.fitness_fn)(&mut net);
+        net.set_fitness(fitness);
+        net
+    }
+
+    /// Best perturbed net sampled so far, or the centre before training
+    pub fn extract_best(&self) -> EvoNet {
+        match self.best.as_ref() {
+            Some(net) => net.clone(),
+            None => self.extract_center(),
+        }
+    }
+
+    pub fn train(&mut self, generations: usize) {
+        let mut rng = rand::thread_rng();
+        (0..generations).for_each(|_| {
+            let center = self.center.get_weights();
+            let n = center.len();
+
+            let noise: Vec<Vec<f64>> = (0..self.pairs)
+                .map(|_| (0..n).map(|_| rng.sample::<f64, _>(StandardNormal)).collect())
+                .collect();
+
+            // Evaluates +epsilon at 2i and -epsilon at 2i + 1
+            let samples: Vec<EvoNet> = noise.iter().flat_map(|eps| [1.0, -1.0].map(|sign| {
+                let weights: Vec<f64> = center.iter().zip(eps.iter()).map(|(w, e)| w + sign * self.noise_std * e).collect();
+                let mut net = self.center.clone();
+                net.set_weights(&weights);
+                let fitness = (self.fitness_fn)(&mut net);
+                net.set_fitness(fitness);
+                net
+            })).collect();
+
+            self.record_generation(&samples);
+            let shaped = self.centered_ranks(&samples);
+
+            let scale = 1.0 / (2.0 * self.pairs as f64 * self.noise_std);
+            let mut gradient = vec![0.0; n];
+            for (i, eps) in noise.iter().enumerate() {
+                let difference = shaped[2 * i] - shaped[2 * i + 1];
+                gradient.iter_mut().zip(eps.iter()).for_each(|(g, e)| *g += scale * difference * e);
+            }
+            gradient.iter_mut().zip(center.iter()).for_each(|(g, w)| *g -= self.weight_decay * w);
+
+            let step = self.optimizer_step(&gradient);
+            let updated: Vec<f64> = center.iter().zip(step.iter()).map(|(w, s)| w + s).collect();
+            self.center.set_weights(&updated);
+
+            let best = samples.into_iter()
+                .reduce(|best, net| if self.objective.is_better(net.get_fitness(), best.get_fitness()) { net } else { best })
+                .unwrap();
+            let improved = match self.best.as_ref() {
+                Some(current) => self.objective.is_better(best.get_fitness(), current.get_fitness()),
+                None => true,
+            };
+            if improved {
+                self.best = Some(best);
+            }
+
+            self.generation += 1;
+        });
+    }
+
+    /// Ranks the oriented fitness and maps it evenly onto -0.5..=0.5
+    fn centered_ranks(&self, samples: &[EvoNet]) -> Vec<f64> {
+        let mut order: Vec<usize> = (0..samples.len()).collect();
+        order.sort_by(|a, b| self.objective.compare(samples[*a].get_fitness(), samples[*b].get_fitness()));
+
+        let mut shaped = vec![0.0; samples.len()];
+        let denominator = (samples.len() - 1) as f64;
+        for (rank, index) in order.iter().enumerate() {
+            shaped[*index] = rank as f64 / denominator - 0.5;
+        }
+        shaped
+    }
+
+    fn optimizer_step(&mut self, gradient: &[f64]) -> Vec<f64> {
+        match self.optimizer {
+            GradientOptimizer::Sgd(momentum) => {
+                self.first_moment.iter_mut().zip(gradient.iter()).for_each(|(v, g)| *v = momentum * *v + (1.0 - momentum) * g);
+                self.first_moment.iter().map(|v| self.learning_rate * v).collect()
+            },
+            GradientOptimizer::Adam(beta1, beta2) => {
+                let t = (self.generation + 1) as i32;
+                let rate = self.learning_rate * (1.0 - beta2.powi(t)).sqrt() / (1.0 - beta1.powi(t));
+                self.first_moment.iter_mut().zip(gradient.iter()).for_each(|(m, g)| *m = beta1 * *m + (1.0 - beta1) * g);
+                self.second_moment.iter_mut().zip(gradient.iter()).for_each(|(v, g)| *v = beta2 * *v + (1.0 - beta2) * g * g);
+                self.first_moment.iter().zip(self.second_moment.iter()).map(|(m, v)| rate * m / (v.sqrt() + 1e-8)).collect()
+            },
+        }
+    }
+
+    fn record_generation(&mut self, samples: &[EvoNet]) {
+        let fitnesses: Vec<f64> = samples.iter().map(|net| net.get_fitness()).collect();
+        self.statistics.push(GenerationStats::from_fitnesses(self.generation, &fitnesses, self.objective));
+    }
+}
+
+pub struct NesBuilder<'a> {
+    architecture: Option<&'a [usize]>,
+    fitness_function: Option<fn(&mut EvoNet) -> f64>,
+    objective: Objective,
+    pairs: Option<usize>,
+    noise_std: f64,
+    learning_rate: f64,
+    optimizer: GradientOptimizer,
+    weight_decay: f64,
+}
+
+impl Default for NesBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a> NesBuilder<'a> {
+
+    pub fn new() -> Self {
+        Self {
+            architecture: None,
+            fitness_function: None,
+            objective: Objective::Maximize,
+            pairs: None,
+            noise_std: 0.05,
+            learning_rate: 0.01,
+            optimizer: GradientOptimizer::default(),
+            weight_decay: 0.0,
+        }
+    }
+
+    pub fn build(&self) -> Result<NesTrainer, TrainerBuildError> {
+        let arch = self.architecture.ok_or(TrainerBuildError::VariableNotSet(String::from("architecture not set")))?;
+        let ft_fn = self.fitness_function.ok_or(TrainerBuildError::VariableNotSet(String::from("fitness_function not set")))?;
+        let pairs = self.pairs.ok_or(TrainerBuildError::VariableNotSet(String::from("population_size not set")))?;
+
+        if arch.contains(&0) {
+            return Err(TrainerBuildError::ValidationError(String::from("architecure cannot contain 0's")));
+        }
+
+        if pairs == 0 {
+            return Err(TrainerBuildError::ValidationError(String::from("population_size must be greater than 0")));
+        }
+
+        if self.noise_std <= 0.0 || self.learning_rate <= 0.0 {
+            return Err(TrainerBuildError::ValidationError(String::from("noise_std and learning_rate must be greater than 0.0")));
+        }
+
+        if self.weight_decay < 0.0 {
+            return Err(TrainerBuildError::ValidationError(String::from("weight_decay cannot be negative")));
+        }
+
+        let valid_optimizer = match self.optimizer {
+            GradientOptimizer::Sgd(momentum) => (0.0..1.0).contains(&momentum),
+            GradientOptimizer::Adam(beta1, beta2) => (0.0..1.0).contains(&beta1) && (0.0..1.0).contains(&beta2),
+        };
+        if !valid_optimizer {
+            return Err(TrainerBuildError::ValidationError(String::from("optimizer coefficients must be between 0.0..1.0")));
+        }
+
+        let center = EvoNet::new(arch);
+        let dimension = center.weight_count();
+
+        Ok(NesTrainer {
+            center,
+            fitness_fn: ft_fn,
+            objective: self.objective,
+            pairs,
+            noise_std: self.noise_std,
+            learning_rate: self.learning_rate,
+            optimizer: self.optimizer,
+            weight_decay: self.weight_decay,
+            first_moment: vec![0.0; dimension],
+            second_moment: vec![0.0; dimension],
+            best: None,
+            generation: 0,
+            statistics: Vec::new(),
+        })
+    }
+
+    pub fn set_architecture(&mut self, architecture: &'a[usize]) {
+        self.architecture = Some(architecture);
+    }
+
+    pub fn set_fitness_function(&mut self, fit_fn: fn(&mut EvoNet) -> f64) {
+        self.fitness_function = Some(fit_fn);
+    }
+
+    /// Defaults to `Objective::Maximize`
+    pub fn set_objective(&mut self, objective: Objective) {
+        self.objective = objective;
+    }
+
+    /// Antithetic pairs sampled each generation, evaluating twice as many nets
+    pub fn set_population_size(&mut self, pairs: usize) {
+        self.pairs = Some(pairs);
+    }
+
+    /// Standard deviation of the perturbations. Defaults to 0.05
+    pub fn set_noise_std(&mut self, std_deviation: f64) {
+        self.noise_std = std_deviation;
+    }
+
+    /// Defaults to 0.01
+    pub fn set_learning_rate(&mut self, rate: f64) {
+        self.learning_rate = rate;
+    }
+
+    /// Defaults to `GradientOptimizer::Adam(0.9, 0.999)`
+    pub fn set_optimizer(&mut self, optimizer: GradientOptimizer) {
+        self.optimizer = optimizer;
+    }
+
+    /// L2 coefficient pulling the centre towards zero. Defaults to 0.0
+    pub fn set_weight_decay(&mut self, decay: f64) {
+        self.weight_decay = decay;
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{evonet::EvoNet, genome::Genome};
+
+/// Settings for rewarding behavioural novelty instead of, or blended with, fitness
+#[derive(Debug)]
+pub struct NoveltyConfig<G = EvoNet> {
+    /// Maps a net to its behaviour characterisation
+    pub descriptor_fn: fn(&mut G) -> Vec<f64>,
+    /// k of the k-nearest-neighbour novelty score
+    pub neighbours: usize,
+    /// Novelty above which a behaviour is added to the archive
+    pub archive_threshold: f64,
+    /// Share of the selection value taken from fitness, 0.0 is pure
+    /// novelty search and 1.0 ignores novelty. Both are normalised
+    /// to 0.0..=1.0 over the population before blending
+    pub fitness_weight: f64,
+}
+
+// Function pointers are copyable for any genome, derive would demand G: Copy
+impl <G> Clone for NoveltyConfig<G> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl <G> Copy for NoveltyConfig<G> {}
+
+/// Behaviours judged novel in earlier generations
+#[derive(Clone)]
+pub struct NoveltyArchive<G = EvoNet> {
+    config: NoveltyConfig<G>,
+    archive: Vec<Vec<f64>>,
+}
+
+impl <G: Genome> NoveltyArchive<G> {
+    pub fn new(config: NoveltyConfig<G>) -> Self {
+        Self { config, archive: Vec::new() }
+    }
+
+    pub fn get_config(&self) -> &NoveltyConfig<G> {
+        &self.config
+    }
+
+    pub fn get_archive(&self) -> &[Vec<f64>] {
+        &self.archive
+    }
+
+    /// Describes the behaviour of every member of the population, each
+    /// starting from a reset state like in the fitness evaluation
+    pub fn describe(&self, population: &mut [G]) -> Vec<Vec<f64>> {
+        population.iter_mut().map(|net| {
+            net.reset_state();
+            (self.config.descriptor_fn)(net)
+        }).collect()
+    }
+
+    /// Mean distance of each behaviour to its k nearest neighbours among the
+    /// rest of the population and the archive. Behaviours scoring above the
+    /// archive threshold are archived afterwards
+    pub fn score(&mut self, behaviours: &[Vec<f64>]) -> Vec<f64> {
+        let novelty: Vec<f64> = behaviours.iter().enumerate().map(|(i, behaviour)| {
+            let mut distances: Vec<f64> = behaviours.iter().enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other)| Self::distance(behaviour, other))
+                .chain(self.archive.iter().map(|other| Self::distance(behaviour, other)))
+                .collect();
+            distances.sort_by(|a, b| a.total_cmp(b));
+
+            let k = self.config.neighbours.min(distances.len());
+            if k == 0 {
+                return 0.0;
+            }
+            distances[..k].iter().sum::<f64>() / k as f64
+        }).collect();
+
+        behaviours.iter().zip(novelty.iter()).for_each(|(behaviour, n)| {
+            if *n > self.config.archive_threshold {
+                self.archive.push(behaviour.clone());
+            }
+        });
+
+        novelty
+    }
+
+    /// Blends oriented fitness with novelty into the selection value
+    pub fn blend(&self, oriented_fitness: &[f64], novelty: &[f64]) -> Vec<f64> {
+        let fitness = Self::normalise(oriented_fitness);
+        let novelty = Self::normalise(novelty);
+        fitness.iter().zip(novelty.iter())
+            .map(|(f, n)| self.config.fitness_weight * f + (1.0 - self.config.fitness_weight) * n)
+            .collect()
+    }
+
+    fn normalise(values: &[f64]) -> Vec<f64> {
+        let min = values.iter().fold(f64::INFINITY, |min, v| min.min(*v));
+        let max = values.iter().fold(f64::NEG_INFINITY, |max, v| max.max(*v));
+        values.iter().map(|v| if max > min { (v - min) / (max - min) } else { 0.0 }).collect()
+    }
+
+    fn distance(a: &[f64], b: &[f64]) -> f64 {
+        a.iter().zip(b.iter()).fold(0.0, |sum, (x, y)| sum + (x - y).powi(2)).sqrt()
+    }
+}
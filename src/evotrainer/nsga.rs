@@ -0,0 +1,440 @@
+use std::io::Write;
+use rand::Rng;
+use crate::evonet::EvoNet;
+use super::{
+    crossover::CrossoverOperator,
+    mutation::MutationOperator,
+    objective::Objective,
+    trainer_builder::TrainerBuildError
+};
+
+/// Member of the current Pareto front
+#[derive(Clone)]
+pub struct ParetoMember {
+    pub net: EvoNet,
+    /// Raw objective values in the order of the trainer's objectives
+    pub objectives: Vec<f64>,
+}
+
+/// Summary of one trained multi-objective generation
+#[derive(Clone, Debug, Default)]
+pub struct MultiObjectiveStats {
+    pub generation: usize,
+    /// Members in the first non-dominated front
+    pub front_size: usize,
+    /// Hypervolume of the first front, when a reference point is set
+    pub hypervolume: Option<f64>,
+}
+
+/// Multi-objective trainer using non-dominated sorting
+/// and crowding distance selection (NSGA-II)
+pub struct NsgaTrainer {
+    population: Vec<EvoNet>,
+    scores: Vec<Vec<f64>>,
+    ranks: Vec<usize>,
+    crowding: Vec<f64>,
+    objectives_fn: fn(&mut EvoNet) -> Vec<f64>,
+    objectives: Vec<Objective>,
+    crossover_rate: f64,
+    mutation_rate: f64,
+    crossover_operator: CrossoverOperator,
+    mutation_operator: MutationOperator,
+    reference_point: Option<Vec<f64>>,
+    generation: usize,
+    statistics: Vec<MultiObjectiveStats>
+}
+
+impl NsgaTrainer {
+    pub fn get_statistics(&self) -> &[MultiObjectiveStats] {
+        &self.statistics
+    }
+
+    pub fn get_objectives(&self) -> &[Objective] {
+        &self.objectives
+    }
+
+    pub fn train(&mut self, generations: usize) {
+        (0..generations).for_each(|_| {
+            let size = self.population.len();
+            for mut net in self.create_offspring() {
+                let score = (self.objectives_fn)(&mut net);
+                assert_eq!(score.len(), self.objectives.len(), "objectives_function must return one value per objective");
+                self.population.push(net);
+                self.scores.push(score);
+            }
+
+            self.select_survivors(size);
+            self.generation += 1;
+
+            let front = self.ranks.iter().filter(|r| **r == 0).count();
+            self.statistics.push(MultiObjectiveStats {
+                generation: self.generation,
+                front_size: front,
+                hypervolume: self.reference_point.as_ref().map(|reference| self.hypervolume(reference)),
+            });
+        });
+    }
+
+    /// Members of the first non-dominated front
+    pub fn pareto_front(&self) -> Vec<ParetoMember> {
+        (0..self.population.len())
+            .filter(|i| self.ranks[*i] == 0)
+            .map(|i| ParetoMember { net: self.population[i].clone(), objectives: self.scores[i].clone() })
+            .collect()
+    }
+
+    /// Writes the Pareto front as CSV, one member per row with its raw
+    /// objective values, its architecture and its flattened weights.
+    /// Architecture and weights are space separated
+    pub fn export_front<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let header: Vec<String> = (0..self.objectives.len()).map(|i| format!("objective_{}", i)).collect();
+        writeln!(writer, "{},architecture,weights", header.join(","))?;
+
+        for member in self.pareto_front() {
+            let objectives: Vec<String> = member.objectives.iter().map(|o| o.to_string()).collect();
+            let architecture: Vec<String> = member.net.get_architecture().iter().map(|n| n.to_string()).collect();
+            let weights: Vec<String> = member.net.get_weights().iter().map(|w| w.to_string()).collect();
+            writeln!(writer, "{},{},{}", objectives.join(","), architecture.join(" "), weights.join(" "))?;
+        }
+        Ok(())
+    }
+
+    /// Volume of objective space dominated by the first front and bounded by
+    /// the reference point, which has to be worse than the front in every objective
+    pub fn hypervolume(&self, reference: &[f64]) -> f64 {
+        // Orient every objective as minimisation
+        let to_min = |values: &[f64]| -> Vec<f64> {
+            values.iter().zip(self.objectives.iter()).map(|(v, o)| -o.orient(*v)).collect()
+        };
+        let reference = to_min(reference);
+        let points: Vec<Vec<f64>> = (0..self.population.len())
+            .filter(|i| self.ranks[*i] == 0)
+            .map(|i| to_min(&self.scores[i]))
+            .filter(|p| p.iter().zip(reference.iter()).all(|(v, r)| v < r))
+            .collect();
+
+        Self::slice_volume(points, &reference)
+    }
+
+    /// Hypervolume by slicing objectives, for minimised points within the reference
+    fn slice_volume(mut points: Vec<Vec<f64>>, reference: &[f64]) -> f64 {
+        if points.is_empty() {
+            return 0.0;
+        }
+
+        let last = reference.len() - 1;
+        if last == 0 {
+            return reference[0] - points.iter().fold(f64::INFINITY, |min, p| min.min(p[0]));
+        }
+
+        points.sort_by(|a, b| a[last].total_cmp(&b[last]));
+        let mut volume = 0.0;
+        for i in 0..points.len() {
+            let upper = if i + 1 < points.len() { points[i + 1][last] } else { reference[last] };
+            let height = upper - points[i][last];
+            if height <= 0.0 {
+                continue;
+            }
+
+            let projected: Vec<Vec<f64>> = points[..=i].iter().map(|p| p[..last].to_vec()).collect();
+            volume += height * Self::slice_volume(projected, &reference[..last]);
+        }
+        volume
+    }
+
+    /// True if score `a` is at least as good in every objective and better in one
+    fn dominates(&self, a: &[f64], b: &[f64]) -> bool {
+        let mut better = false;
+        for ((x, y), objective) in a.iter().zip(b.iter()).zip(self.objectives.iter()) {
+            if objective.is_better(*y, *x) {
+                return false;
+            }
+            if objective.is_better(*x, *y) {
+                better = true;
+            }
+        }
+        better
+    }
+
+    /// Sorts the combined parents and offspring into non-dominated fronts and
+    /// keeps the best fronts up to `target` members, breaking the last front
+    /// by crowding distance
+    fn select_survivors(&mut self, target: usize) {
+        let size = self.population.len();
+        let fronts = self.non_dominated_sort();
+
+        let mut ranks = vec![0; size];
+        let mut crowding = vec![0.0; size];
+        let mut keep: Vec<usize> = Vec::with_capacity(target);
+        for (rank, front) in fronts.iter().enumerate() {
+            front.iter().for_each(|i| ranks[*i] = rank);
+            self.crowding_distance(front).into_iter().for_each(|(i, d)| crowding[i] = d);
+
+            if keep.len() + front.len() <= target {
+                keep.extend_from_slice(front);
+            } else {
+                let mut last = front.clone();
+                last.sort_by(|a, b| crowding[*b].total_cmp(&crowding[*a]));
+                keep.extend_from_slice(&last[..target - keep.len()]);
+            }
+
+            if keep.len() == target {
+                break;
+            }
+        }
+
+        let mut population: Vec<Option<EvoNet>> = self.population.drain(..).map(Some).collect();
+        self.population = keep.iter().map(|i| population[*i].take().unwrap()).collect();
+        self.scores = keep.iter().map(|i| self.scores[*i].clone()).collect();
+        self.ranks = keep.iter().map(|i| ranks[*i]).collect();
+        self.crowding = keep.iter().map(|i| crowding[*i]).collect();
+    }
+
+    fn non_dominated_sort(&self) -> Vec<Vec<usize>> {
+        let size = self.scores.len();
+        let mut dominated_by: Vec<Vec<usize>> = vec![Vec::new(); size];
+        let mut domination_count = vec![0; size];
+        let mut fronts: Vec<Vec<usize>> = vec![Vec::new()];
+
+        for p in 0..size {
+            for q in 0..size {
+                if self.dominates(&self.scores[p], &self.scores[q]) {
+                    dominated_by[p].push(q);
+                } else if self.dominates(&self.scores[q], &self.scores[p]) {
+                    domination_count[p] += 1;
+                }
+            }
+            if domination_count[p] == 0 {
+                fronts[0].push(p);
+            }
+        }
+
+        let mut f = 0;
+        while !fronts[f].is_empty() {
+            let mut next: Vec<usize> = Vec::new();
+            for p in fronts[f].iter() {
+                for q in dominated_by[*p].iter() {
+                    domination_count[*q] -= 1;
+                    if domination_count[*q] == 0 {
+                        next.push(*q);
+                    }
+                }
+            }
+            fronts.push(next);
+            f += 1;
+        }
+        fronts.pop();
+        fronts
+    }
+
+    /// (population index, crowding distance) of each front member
+    fn crowding_distance(&self, front: &[usize]) -> Vec<(usize, f64)> {
+        let mut distance = vec![0.0; front.len()];
+        for m in 0..self.objectives.len() {
+            let mut order: Vec<usize> = (0..front.len()).collect();
+            order.sort_by(|a, b| self.scores[front[*a]][m].total_cmp(&self.scores[front[*b]][m]));
+
+            let min = self.scores[front[order[0]]][m];
+            let max = self.scores[front[order[order.len() - 1]]][m];
+            distance[order[0]] = f64::INFINITY;
+            distance[order[order.len() - 1]] = f64::INFINITY;
+            if max == min {
+                continue;
+            }
+
+            for k in 1..order.len().saturating_sub(1) {
+                let gap = self.scores[front[order[k + 1]]][m] - self.scores[front[order[k - 1]]][m];
+                distance[order[k]] += gap / (max - min);
+            }
+        }
+        front.iter().copied().zip(distance).collect()
+    }
+
+    /// Binary tournament on rank then crowding distance
+    fn tournament<R: Rng>(&self, rng: &mut R) -> usize {
+        let a = rng.gen_range(0..self.population.len());
+        let b = rng.gen_range(0..self.population.len());
+        if self.ranks[a] != self.ranks[b] {
+            if self.ranks[a] < self.ranks[b] { a } else { b }
+        } else if self.crowding[a] >= self.crowding[b] {
+            a
+        } else {
+            b
+        }
+    }
+
+    fn create_offspring(&self) -> Vec<EvoNet> {
+        let mut rng = rand::thread_rng();
+        (0..self.population.len()).map(|_| {
+            let a = self.tournament(&mut rng);
+            let mut child = if rng.gen_range(0.0..1.0) < self.crossover_rate {
+                let b = self.tournament(&mut rng);
+                EvoNet::from_many_parents(&[&self.population[a], &self.population[b]], &[1.0, 1.0], self.crossover_operator)
+            } else {
+                self.population[a].clone()
+            };
+            child.mutate_with(self.mutation_rate, self.mutation_operator);
+            child
+        }).collect()
+    }
+}
+
+pub struct NsgaBuilder<'a> {
+    population_size: Option<usize>,
+    architecture: Option<&'a [usize]>,
+    objectives_function: Option<fn(&mut EvoNet) -> Vec<f64>>,
+    objectives: Vec<Objective>,
+    crossover_rate: Option<f64>,
+    mutation_rate: Option<f64>,
+    crossover_operator: CrossoverOperator,
+    mutation_operator: MutationOperator,
+    reference_point: Option<Vec<f64>>,
+}
+
+impl Default for NsgaBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a> NsgaBuilder<'a> {
+
+    pub fn new() -> Self {
+        Self {
+            population_size: None,
+            architecture: None,
+            objectives_function: None,
+            objectives: Vec::new(),
+            crossover_rate: None,
+            mutation_rate: None,
+            crossover_operator: CrossoverOperator::Uniform,
+            mutation_operator: MutationOperator::default(),
+            reference_point: None,
+        }
+    }
+
+    pub fn build(&self) -> Result<NsgaTrainer, TrainerBuildError> {
+        let pop_size = self.population_size.ok_or(TrainerBuildError::VariableNotSet(String::from("population_size not set")))?;
+        let arch = self.architecture.ok_or(TrainerBuildError::VariableNotSet(String::from("architecture not set")))?;
+        let obj_fn = self.objectives_function.ok_or(TrainerBuildError::VariableNotSet(String::from("objectives_function not set")))?;
+        let cross_rate = self.crossover_rate.unwrap_or(0.9);
+        let mut_rate = self.mutation_rate.unwrap_or(0.1);
+
+        if pop_size <= 1 {
+            return Err(TrainerBuildError::ValidationError(String::from("population_size must be greater than 1")));
+        }
+
+        if arch.contains(&0) {
+            return Err(TrainerBuildError::ValidationError(String::from("architecure cannot contain 0's")));
+        }
+
+        if self.objectives.len() < 2 {
+            return Err(TrainerBuildError::ValidationError(String::from("multi-objective training needs at least 2 objectives")));
+        }
+
+        if !(0.0..=1.0).contains(&cross_rate) {
+            return Err(TrainerBuildError::ValidationError(String::from("crossover_rate must be between 0.0..=1.0")));
+        }
+
+        if !(0.0..=1.0).contains(&mut_rate) {
+            return Err(TrainerBuildError::ValidationError(String::from("mutation_rate must be between 0.0..=1.0")));
+        }
+
+        if let Some(reference) = self.reference_point.as_ref() {
+            if reference.len() != self.objectives.len() {
+                return Err(TrainerBuildError::ValidationError(String::from("reference_point needs one value per objective")));
+            }
+        }
+
+        let mut population: Vec<EvoNet> = Vec::with_capacity(pop_size);
+        let mut scores: Vec<Vec<f64>> = Vec::with_capacity(pop_size);
+        for _ in 0..pop_size {
+            let mut net = EvoNet::new(arch);
+            let score = (obj_fn)(&mut net);
+            if score.len() != self.objectives.len() {
+                return Err(TrainerBuildError::ValidationError(String::from("objectives_function must return one value per objective")));
+            }
+            population.push(net);
+            scores.push(score);
+        }
+
+        let mut trainer = NsgaTrainer {
+            population,
+            scores,
+            ranks: Vec::new(),
+            crowding: Vec::new(),
+            objectives_fn: obj_fn,
+            objectives: self.objectives.clone(),
+            crossover_rate: cross_rate,
+            mutation_rate: mut_rate,
+            crossover_operator: self.crossover_operator,
+            mutation_operator: self.mutation_operator,
+            reference_point: self.reference_point.clone(),
+            generation: 0,
+            statistics: Vec::new(),
+        };
+
+        // Rank the initial population, selecting all of it
+        trainer.select_survivors(pop_size);
+
+        Ok(trainer)
+    }
+
+    pub fn set_population_size(&mut self, size: usize) {
+        self.population_size = Some(size);
+    }
+
+    pub fn set_architecture(&mut self, architecture: &'a[usize]) {
+        self.architecture = Some(architecture);
+    }
+
+    /// Function scoring a net on every objective, returning
+    /// one value per entry of `objectives` in the same order
+    pub fn set_objectives_function(&mut self, obj_fn: fn(&mut EvoNet) -> Vec<f64>, objectives: Vec<Objective>) {
+        self.objectives_function = Some(obj_fn);
+        self.objectives = objectives;
+    }
+
+    /// Chance an offspring is recombined from two parents
+    /// instead of cloned from one. Defaults to 0.9
+    pub fn set_crossover_rate(&mut self, rate: f64) {
+        self.crossover_rate = Some(rate);
+    }
+
+    /// Defaults to 0.1
+    pub fn set_mutation_rate(&mut self, rate: f64) {
+        self.mutation_rate = Some(rate);
+    }
+
+    /// Defaults to `CrossoverOperator::Uniform`
+    pub fn set_crossover_operator(&mut self, operator: CrossoverOperator) {
+        self.crossover_operator = operator;
+    }
+
+    /// Defaults to `MutationOperator::Gaussian(0.1)`
+    pub fn set_mutation_operator(&mut self, operator: MutationOperator) {
+        self.mutation_operator = operator;
+    }
+
+    /// Raw objective values bounding the hypervolume reported in the statistics
+    pub fn set_reference_point(&mut self, reference: Vec<f64>) {
+        self.reference_point = Some(reference);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NsgaTrainer;
+
+    #[test]
+    fn hypervolume_of_a_staircase_front() {
+        let front = vec![vec![1.0, 3.0], vec![2.0, 2.0], vec![3.0, 1.0]];
+        assert_eq!(NsgaTrainer::slice_volume(front, &[4.0, 4.0]), 6.0);
+    }
+
+    #[test]
+    fn hypervolume_of_a_single_point() {
+        assert_eq!(NsgaTrainer::slice_volume(vec![vec![1.0, 2.0]], &[3.0, 5.0]), 6.0);
+        assert_eq!(NsgaTrainer::slice_volume(Vec::new(), &[3.0, 5.0]), 0.0);
+    }
+}
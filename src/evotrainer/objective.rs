@@ -0,0 +1,32 @@
+use std::cmp::Ordering;
+
+/// Direction the trainer optimises the fitness function in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Objective {
+    /// Higher fitness is better
+    #[default]
+    Maximize,
+    /// Lower fitness is better, e.g. for loss functions
+    Minimize
+}
+
+impl Objective {
+    /// Maps a raw fitness onto the selection scale where
+    /// a bigger value is always better
+    pub fn orient(&self, fitness: f64) -> f64 {
+        match self {
+            Objective::Maximize => fitness,
+            Objective::Minimize => -fitness,
+        }
+    }
+
+    /// Orders two raw fitness values so that the better one is `Greater`
+    pub fn compare(&self, a: f64, b: f64) -> Ordering {
+        self.orient(a).total_cmp(&self.orient(b))
+    }
+
+    /// True if raw fitness `a` is strictly better than raw fitness `b`
+    pub fn is_better(&self, a: f64, b: f64) -> bool {
+        self.compare(a, b) == Ordering::Greater
+    }
+}
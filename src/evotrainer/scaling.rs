@@ -0,0 +1,110 @@
+use crate::evotrainer::evotrainer::FitnessPair;
+
+/// Transformation applied to the population fitness before parent selection.
+/// Only the `FitnessPair` values handed to the selection strategies are
+/// transformed, each `EvoNet` keeps reporting its raw fitness.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FitnessScaling {
+    /// Selection reads the raw fitness
+    #[default]
+    None,
+    /// (multiplier)
+    /// Linearly scales fitness so the mean is preserved and the best
+    /// individual receives `multiplier` times the mean. Clamped so no
+    /// individual drops below 0.0. Negative fitness is first shifted so
+    /// the worst individual sits at 0.0
+    Linear(f64),
+    /// (c)
+    /// Subtracts `mean - c * std_deviation` from every fitness and
+    /// truncates negative values to 0.0
+    SigmaTruncation(f64),
+    /// (exponent)
+    /// Raises the fitness, shifted so the worst individual is at 0.0,
+    /// to the given power
+    PowerLaw(f64),
+    /// Replaces fitness with its rank normalised to 0.0..=1.0.
+    /// Tied individuals share their average rank
+    Rank,
+    /// Replaces fitness with its standard score (fitness - mean) / std_deviation
+    ZScore
+}
+
+impl FitnessScaling {
+    /// Transforms the fitness of every pair in place.
+    /// Expects the pairs sorted from low fitness to high fitness,
+    /// every transformation is monotonic so the order is preserved
+    pub fn apply(&self, fitness_pairs: &mut [FitnessPair]) {
+        if fitness_pairs.is_empty() {
+            return;
+        }
+
+        let n = fitness_pairs.len() as f64;
+        let (sum, sum_sq) = fitness_pairs.iter().fold((0.0, 0.0), |(sum, sum_sq), pair| {
+            (sum + pair.fitness, sum_sq + pair.fitness * pair.fitness)
+        });
+        let mean = sum / n;
+        let std_deviation = ((sum_sq / n) - (mean * mean)).max(0.0).sqrt();
+        let min = fitness_pairs.iter().fold(f64::INFINITY, |min, pair| min.min(pair.fitness));
+        let max = fitness_pairs.iter().fold(f64::NEG_INFINITY, |max, pair| max.max(pair.fitness));
+
+        match *self {
+            FitnessScaling::None => {},
+            FitnessScaling::Linear(multiplier) => {
+                // Goldberg's formula needs a positive mean, shift negative
+                // fitness so the worst individual sits at 0.0 first
+                let shift = min.min(0.0);
+                let (mean, min, max) = (mean - shift, min - shift, max - shift);
+                let (a, b) = if max == mean {
+                    (1.0, 0.0)
+                } else if min > (multiplier * mean - max) / (multiplier - 1.0) {
+                    let delta = max - mean;
+                    let a = (multiplier - 1.0) * mean / delta;
+                    (a, mean * (max - multiplier * mean) / delta)
+                } else {
+                    // Cannot reach the multiplier without going negative,
+                    // scale so the worst individual sits at 0.0 instead
+                    let delta = mean - min;
+                    (mean / delta, -min * mean / delta)
+                };
+
+                fitness_pairs.iter_mut().for_each(|pair| {
+                    pair.fitness = (a * (pair.fitness - shift) + b).max(0.0);
+                });
+            },
+            FitnessScaling::SigmaTruncation(c) => {
+                let floor = mean - c * std_deviation;
+                fitness_pairs.iter_mut().for_each(|pair| {
+                    pair.fitness = (pair.fitness - floor).max(0.0);
+                });
+            },
+            FitnessScaling::PowerLaw(exponent) => {
+                fitness_pairs.iter_mut().for_each(|pair| {
+                    pair.fitness = (pair.fitness - min).powf(exponent);
+                });
+            },
+            FitnessScaling::Rank => {
+                let denominator = (fitness_pairs.len() - 1).max(1) as f64;
+                let mut i = 0;
+                while i < fitness_pairs.len() {
+                    let mut j = i;
+                    while j + 1 < fitness_pairs.len() && fitness_pairs[j + 1].fitness == fitness_pairs[i].fitness {
+                        j += 1;
+                    }
+
+                    let rank = (i + j) as f64 / 2.0 / denominator;
+                    fitness_pairs[i..=j].iter_mut().for_each(|pair| pair.fitness = rank);
+                    i = j + 1;
+                }
+            },
+            FitnessScaling::ZScore => {
+                fitness_pairs.iter_mut().for_each(|pair| {
+                    pair.fitness = if std_deviation > 0.0 {
+                        (pair.fitness - mean) / std_deviation
+                    } else {
+                        0.0
+                    };
+                });
+            }
+        }
+    }
+}
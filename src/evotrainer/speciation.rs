@@ -0,0 +1,195 @@
+use crate::{evonet::EvoNet, genome::Genome};
+use super::{evotrainer::FitnessPair, objective::Objective};
+
+/// Distance between two genomes' flattened weights
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DistanceMetric {
+    #[default]
+    Euclidean,
+    Manhattan,
+    /// Largest absolute difference of any single weight
+    Chebyshev,
+    /// 1.0 - cosine similarity, ignores the magnitude of the weights
+    Cosine
+}
+
+impl DistanceMetric {
+    pub fn measure(&self, a: &[f64], b: &[f64]) -> f64 {
+        let pairs = a.iter().zip(b.iter());
+        match self {
+            DistanceMetric::Euclidean => pairs.fold(0.0, |sum, (x, y)| sum + (x - y).powi(2)).sqrt(),
+            DistanceMetric::Manhattan => pairs.fold(0.0, |sum, (x, y)| sum + (x - y).abs()),
+            DistanceMetric::Chebyshev => pairs.fold(0.0, |max: f64, (x, y)| max.max((x - y).abs())),
+            DistanceMetric::Cosine => {
+                let (dot, norm_a, norm_b) = pairs.fold((0.0, 0.0, 0.0), |(dot, norm_a, norm_b), (x, y)| {
+                    (dot + x * y, norm_a + x * x, norm_b + y * y)
+                });
+                if norm_a == 0.0 || norm_b == 0.0 {
+                    return 1.0;
+                }
+                1.0 - dot / (norm_a.sqrt() * norm_b.sqrt())
+            }
+        }
+    }
+}
+
+/// Settings for splitting the population into species
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeciationConfig {
+    /// Genome distance compared against the compatibility threshold
+    pub distance: DistanceMetric,
+    /// Starting distance below which a genome joins a species
+    pub compatibility_threshold: f64,
+    /// Species count the threshold adapts towards. 0 keeps the threshold fixed
+    pub target_species: usize,
+    /// Amount the threshold moves each generation the species count misses the target
+    pub threshold_step: f64,
+    /// Generations without improvement after which a species stops
+    /// receiving offspring. The species holding the best genome is never culled
+    pub stagnation_limit: usize,
+}
+
+impl Default for SpeciationConfig {
+    fn default() -> Self {
+        Self {
+            distance: DistanceMetric::Euclidean,
+            compatibility_threshold: 3.0,
+            target_species: 10,
+            threshold_step: 0.1,
+            stagnation_limit: 15,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Species<G = EvoNet> {
+    pub id: usize,
+    /// Genome new members are compared against, the best member of the last generation
+    pub representative: G,
+    /// Population indices of the members
+    pub members: Vec<usize>,
+    /// Best raw fitness the species has reached
+    pub best_fitness: f64,
+    /// Generations since `best_fitness` improved
+    pub stagnation: usize,
+}
+
+/// Keeps the species of a trainer's population across generations
+#[derive(Clone)]
+pub struct SpeciesTracker<G = EvoNet> {
+    config: SpeciationConfig,
+    threshold: f64,
+    species: Vec<Species<G>>,
+    next_id: usize,
+}
+
+impl <G: Genome> SpeciesTracker<G> {
+    pub fn new(config: SpeciationConfig) -> Self {
+        Self {
+            threshold: config.compatibility_threshold,
+            config,
+            species: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn get_species(&self) -> &[Species<G>] {
+        &self.species
+    }
+
+    pub fn get_threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Assigns every member of the population to the first species whose
+    /// representative is within the compatibility threshold, founding new
+    /// species as needed. Updates stagnation, representatives and the threshold
+    pub fn speciate(&mut self, population: &[G], objective: Objective) {
+        self.species.iter_mut().for_each(|s| s.members.clear());
+
+        for (i, net) in population.iter().enumerate() {
+            let found = self.species.iter()
+                .position(|s| net.distance_with(&s.representative, self.config.distance) < self.threshold);
+            match found {
+                Some(s) => self.species[s].members.push(i),
+                None => {
+                    self.species.push(Species {
+                        id: self.next_id,
+                        representative: net.clone(),
+                        members: vec![i],
+                        best_fitness: net.get_fitness(),
+                        stagnation: 0,
+                    });
+                    self.next_id += 1;
+                }
+            }
+        }
+
+        self.species.retain(|s| !s.members.is_empty());
+
+        self.species.iter_mut().for_each(|s| {
+            let best = s.members.iter()
+                .copied()
+                .reduce(|best, i| if objective.is_better(population[i].get_fitness(), population[best].get_fitness()) { i } else { best })
+                .unwrap();
+
+            if objective.is_better(population[best].get_fitness(), s.best_fitness) {
+                s.best_fitness = population[best].get_fitness();
+                s.stagnation = 0;
+            } else {
+                s.stagnation += 1;
+            }
+            s.representative = population[best].clone();
+        });
+
+        if self.config.target_species > 0 {
+            if self.species.len() < self.config.target_species {
+                self.threshold = (self.threshold - self.config.threshold_step).max(self.config.threshold_step);
+            } else if self.species.len() > self.config.target_species {
+                self.threshold += self.config.threshold_step;
+            }
+        }
+    }
+
+    /// Amount of offspring each species contributes to the next generation,
+    /// in the order of `get_species`. Proportional to the explicitly shared
+    /// fitness of the members, the selection fitness divided by species size
+    pub fn allocate_offspring(&self, fitness_pairs: &[FitnessPair], objective: Objective) -> Vec<usize> {
+        let population_size = fitness_pairs.len();
+        let mut selection_fitness = vec![0.0; population_size];
+        fitness_pairs.iter().for_each(|pair| selection_fitness[pair.index] = pair.fitness);
+        let min = selection_fitness.iter().fold(f64::INFINITY, |min, ft| min.min(*ft));
+
+        let best_species = (0..self.species.len())
+            .reduce(|best, s| if objective.is_better(self.species[s].best_fitness, self.species[best].best_fitness) { s } else { best })
+            .unwrap_or(0);
+
+        let shared: Vec<f64> = self.species.iter().enumerate().map(|(s, species)| {
+            if s != best_species && species.stagnation >= self.config.stagnation_limit {
+                return 0.0;
+            }
+            species.members.iter().fold(0.0, |sum, i| sum + selection_fitness[*i] - min) / species.members.len() as f64
+        }).collect();
+
+        let shared_sum = shared.iter().sum::<f64>();
+        let exact: Vec<f64> = shared.iter().enumerate().map(|(s, share)| {
+            if shared_sum > 0.0 {
+                population_size as f64 * share / shared_sum
+            } else if s == best_species {
+                // Nothing to tell the species apart, keep the best one alive
+                population_size as f64
+            } else {
+                0.0
+            }
+        }).collect();
+
+        // Largest remainder rounding so the quotas fill the population exactly
+        let mut quotas: Vec<usize> = exact.iter().map(|q| q.floor() as usize).collect();
+        let mut remainders: Vec<usize> = (0..exact.len()).collect();
+        remainders.sort_by(|a, b| (exact[*b] - exact[*b].floor()).total_cmp(&(exact[*a] - exact[*a].floor())));
+        let missing = population_size - quotas.iter().sum::<usize>();
+        remainders.iter().cycle().take(missing).for_each(|s| quotas[*s] += 1);
+
+        quotas
+    }
+}
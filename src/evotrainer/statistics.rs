@@ -0,0 +1,46 @@
+use super::objective::Objective;
+
+/// Summary of one trained generation, taken right after
+/// the population fitness has been calculated
+#[derive(Clone, Debug, Default)]
+pub struct GenerationStats {
+    pub generation: usize,
+    /// Raw fitness of the best individual according to the trainer's `Objective`
+    pub best_fitness: f64,
+    pub mean_fitness: f64,
+    pub std_deviation: f64,
+    /// Probability of each parent selection strategy,
+    /// in the order they were added to the builder
+    pub strategy_weights: Vec<f64>,
+    /// Probability of each crossover operator,
+    /// in the order they were added to the builder
+    pub crossover_operator_weights: Vec<f64>,
+    /// Probability of each mutation operator,
+    /// in the order they were added to the builder
+    pub mutation_operator_weights: Vec<f64>,
+    /// Member count of each species, empty when speciation is disabled
+    pub species_sizes: Vec<usize>,
+    /// Behaviours in the novelty archive, 0 when novelty search is disabled
+    pub novelty_archive_size: usize,
+}
+
+impl GenerationStats {
+    /// Best, mean and standard deviation of the raw fitness of a generation,
+    /// the remaining fields left empty
+    pub fn from_fitnesses(generation: usize, fitnesses: &[f64], objective: Objective) -> GenerationStats {
+        let n = fitnesses.len() as f64;
+        let best = fitnesses.iter().copied()
+            .reduce(|best, ft| if objective.is_better(ft, best) { ft } else { best })
+            .unwrap_or(0.0);
+        let mean = fitnesses.iter().sum::<f64>() / n;
+        let variance = fitnesses.iter().fold(0.0, |sum, ft| sum + (ft - mean).powi(2)) / n;
+
+        GenerationStats {
+            generation,
+            best_fitness: best,
+            mean_fitness: mean,
+            std_deviation: variance.sqrt(),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use rand::Rng;
+use crate::evonet::EvoNet;
+use super::{
+    evotrainer::HasFitness,
+    objective::Objective,
+    statistics::GenerationStats,
+    trainer_builder::TrainerBuildError
+};
+
+/// Which personal bests a particle is attracted to
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SwarmTopology {
+    /// Best of the whole swarm
+    #[default]
+    Global,
+    /// Best of the particles within this many places on either side in a ring (neighbours)
+    Ring(usize),
+}
+
+/// Inertia weight over the generations
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InertiaSchedule {
+    /// (inertia)
+    Constant(f64),
+    /// Linear change, holding the end value afterwards (start, end, generations)
+    Linear(f64, f64, usize),
+}
+
+impl Default for InertiaSchedule {
+    fn default() -> Self {
+        InertiaSchedule::Constant(0.729)
+    }
+}
+
+impl InertiaSchedule {
+    pub fn inertia(&self, generation: usize) -> f64 {
+        match *self {
+            InertiaSchedule::Constant(inertia) => inertia,
+            InertiaSchedule::Linear(start, end, generations) => {
+                let progress = (generation as f64 / generations.max(1) as f64).min(1.0);
+                start + (end - start) * progress
+            },
+        }
+    }
+}
+
+/// A net moving through weight space
+#[derive(Clone)]
+pub struct Particle {
+    pub net: EvoNet,
+    pub velocity: Vec<f64>,
+    pub personal_best: EvoNet,
+}
+
+/// Particle swarm optimisation over the flattened weights of a fixed architecture
+pub struct SwarmTrainer {
+    particles: Vec<Particle>,
+    fitness_fn: fn(&mut EvoNet) -> f64,
+    objective: Objective,
+    topology: SwarmTopology,
+    inertia: InertiaSchedule,
+    cognitive: f64,
+    social: f64,
+    max_velocity: Option<f64>,
+    generation: usize,
+    statistics: Vec<GenerationStats>
+}
+
+impl SwarmTrainer {
+    pub fn get_statistics(&self) -> &[GenerationStats] {
+        &self.statistics
+    }
+
+    pub fn get_particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Best personal best of the swarm
+    pub fn extract_best(&self) -> EvoNet {
+        self.particles.iter()
+            .map(|p| &p.personal_best)
+            .reduce(|best, net| if self.objective.is_better(net.get_fitness(), best.get_fitness()) { net } else { best })
+            .unwrap()
+            .clone()
+    }
+
+    pub fn train(&mut self, generations: usize) {
+        let mut rng = rand::thread_rng();
+        if self.generation == 0 {
+            self.particles.iter_mut().for_each(|p| {
+                let fitness = (self.fitness_fn)(&mut p.net);
+                p.net.set_fitness(fitness);
+                p.personal_best = p.net.clone();
+            });
+        }
+
+        (0..generations).for_each(|_| {
+            let inertia = self.inertia.inertia(self.generation);
+            let bests: Vec<Vec<f64>> = (0..self.particles.len())
+                .map(|i| self.particles[self.neighbourhood_best(i)].personal_best.get_weights())
+                .collect();
+
+            for (particle, social_best) in self.particles.iter_mut().zip(bests.iter()) {
+                let position = particle.net.get_weights();
+                let personal = particle.personal_best.get_weights();
+
+                for (i, v) in particle.velocity.iter_mut().enumerate() {
+                    *v = inertia * *v
+                        + self.cognitive * rng.gen_range(0.0..1.0) * (personal[i] - position[i])
+                        + self.social * rng.gen_range(0.0..1.0) * (social_best[i] - position[i]);
+                    if let Some(max) = self.max_velocity {
+                        *v = v.clamp(-max, max);
+                    }
+                }
+
+                let moved: Vec<f64> = position.iter().zip(particle.velocity.iter()).map(|(x, v)| x + v).collect();
+                particle.net.set_weights(&moved);
+                let fitness = (self.fitness_fn)(&mut particle.net);
+                particle.net.set_fitness(fitness);
+
+                if self.objective.is_better(fitness, particle.personal_best.get_fitness()) {
+                    particle.personal_best = particle.net.clone();
+                }
+            }
+
+            self.record_generation();
+            self.generation += 1;
+        });
+    }
+
+    fn neighbourhood_best(&self, particle: usize) -> usize {
+        let count = self.particles.len();
+        let better = |a: usize, b: usize| {
+            if self.objective.is_better(self.particles[b].personal_best.get_fitness(), self.particles[a].personal_best.get_fitness()) { b } else { a }
+        };
+
+        match self.topology {
+            SwarmTopology::Global => (0..count).reduce(better).unwrap(),
+            SwarmTopology::Ring(neighbours) => {
+                let reach = neighbours.min(count / 2);
+                (0..=2 * reach)
+                    .map(|offset| (particle + count + offset - reach) % count)
+                    .reduce(better)
+                    .unwrap()
+            },
+        }
+    }
+
+    fn record_generation(&mut self) {
+        let fitnesses: Vec<f64> = self.particles.iter().map(|p| p.net.get_fitness()).collect();
+        self.statistics.push(GenerationStats::from_fitnesses(self.generation, &fitnesses, self.objective));
+    }
+}
+
+pub struct SwarmBuilder<'a> {
+    population_size: Option<usize>,
+    architecture: Option<&'a [usize]>,
+    fitness_function: Option<fn(&mut EvoNet) -> f64>,
+    objective: Objective,
+    topology: SwarmTopology,
+    inertia: InertiaSchedule,
+    cognitive: f64,
+    social: f64,
+    max_velocity: Option<f64>,
+}
+
+impl Default for SwarmBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a> SwarmBuilder<'a> {
+
+    pub fn new() -> Self {
+        Self {
+            population_size: None,
+            architecture: None,
+            fitness_function: None,
+            objective: Objective::Maximize,
+            topology: SwarmTopology::default(),
+            inertia: InertiaSchedule::default(),
+            cognitive: 1.49445,
+            social: 1.49445,
+            max_velocity: None,
+        }
+    }
+
+    pub fn build(&self) -> Result<SwarmTrainer, TrainerBuildError> {
+        let pop_size = self.population_size.ok_or(TrainerBuildError::VariableNotSet(String::from("population_size not set")))?;
+        let arch = self.architecture.ok_or(TrainerBuildError::VariableNotSet(String::from("architecture not set")))?;
+        let ft_fn = self.fitness_function.ok_or(TrainerBuildError::VariableNotSet(String::from("fitness_function not set")))?;
+
+        if pop_size < 2 {
+            return Err(TrainerBuildError::ValidationError(String::from("population_size must be greater than 1")));
+        }
+
+        if arch.contains(&0) {
+            return Err(TrainerBuildError::ValidationError(String::from("architecure cannot contain 0's")));
+        }
+
+        if self.topology == SwarmTopology::Ring(0) {
+            return Err(TrainerBuildError::ValidationError(String::from("ring topology needs at least 1 neighbour")));
+        }
+
+        if self.cognitive < 0.0 || self.social < 0.0 {
+            return Err(TrainerBuildError::ValidationError(String::from("acceleration coefficients cannot be negative")));
+        }
+
+        if self.max_velocity.is_some_and(|max| max <= 0.0) {
+            return Err(TrainerBuildError::ValidationError(String::from("max_velocity must be greater than 0.0")));
+        }
+
+        let mut rng = rand::thread_rng();
+        let particles = (0..pop_size).map(|_| {
+            let net = EvoNet::new(arch);
+            let velocity = (0..net.weight_count()).map(|_| rng.gen_range(-0.1..=0.1)).collect();
+            Particle { personal_best: net.clone(), net, velocity }
+        }).collect();
+
+        Ok(SwarmTrainer {
+            particles,
+            fitness_fn: ft_fn,
+            objective: self.objective,
+            topology: self.topology,
+            inertia: self.inertia,
+            cognitive: self.cognitive,
+            social: self.social,
+            max_velocity: self.max_velocity,
+            generation: 0,
+            statistics: Vec::new(),
+        })
+    }
+
+    pub fn set_population_size(&mut self, size: usize) {
+        self.population_size = Some(size);
+    }
+
+    pub fn set_architecture(&mut self, architecture: &'a[usize]) {
+        self.architecture = Some(architecture);
+    }
+
+    pub fn set_fitness_function(&mut self, fit_fn: fn(&mut EvoNet) -> f64) {
+        self.fitness_function = Some(fit_fn);
+    }
+
+    /// Defaults to `Objective::Maximize`
+    pub fn set_objective(&mut self, objective: Objective) {
+        self.objective = objective;
+    }
+
+    /// Defaults to `SwarmTopology::Global`
+    pub fn set_topology(&mut self, topology: SwarmTopology) {
+        self.topology = topology;
+    }
+
+    /// Defaults to `InertiaSchedule::Constant(0.729)`
+    pub fn set_inertia(&mut self, inertia: InertiaSchedule) {
+        self.inertia = inertia;
+    }
+
+    /// Pull towards the particle's own best. Defaults to 1.49445
+    pub fn set_cognitive_coefficient(&mut self, coefficient: f64) {
+        self.cognitive = coefficient;
+    }
+
+    /// Pull towards the neighbourhood best. Defaults to 1.49445
+    pub fn set_social_coefficient(&mut self, coefficient: f64) {
+        self.social = coefficient;
+    }
+
+    /// Clamps every velocity component to -max..=max. Unclamped by default
+    pub fn set_max_velocity(&mut self, max: f64) {
+        self.max_velocity = Some(max);
+    }
+}
@@ -1,18 +1,48 @@
-use std::{error::Error, fmt::Display};
-use crate::evonet::EvoNet;
-use super::{evotrainer::EvoTrainer, crossover::Strategies};
+use std::{error::Error, fmt::Display, mem::discriminant};
+use crate::{evonet::EvoNet, genome::Genome};
+use super::{
+    adaptive::OperatorAdaptation,
+    crossover::{CrossoverOperator, Strategies},
+    initialization::WeightInit,
+    evotrainer::{EvoTrainer, TrainerParams},
+    mating::MatingPolicy,
+    mutation::MutationOperator,
+    novelty::NoveltyConfig,
+    objective::Objective,
+    scaling::FitnessScaling,
+    speciation::SpeciationConfig
+};
 
-pub struct TrainerBuilder<'a> {
+pub struct TrainerBuilder<'a, G: Genome = EvoNet> {
     parent_strategies: Vec<Strategies>,
     population_size: Option<usize>,
     survival_rate: Option<f64>,
     crossover_rate: Option<f64>,
     mutation_rate: Option<f64>,
     architecture: Option<&'a [usize]>, 
-    fitness_function: Option<fn(&mut EvoNet) -> f64>,
+    fitness_function: Option<fn(&mut G) -> f64>,
+    fitness_scaling: FitnessScaling,
+    objective: Objective,
+    mating_policy: MatingPolicy,
+    crossover_operators: Vec<(CrossoverOperator, usize)>,
+    mutation_operators: Vec<(MutationOperator, usize)>,
+    operator_adaptation: OperatorAdaptation,
+    speciation: Option<SpeciationConfig>,
+    novelty: Option<NoveltyConfig<G>>,
+    genome_config: G::Config,
+    weight_init: Option<WeightInit>,
+    /// (layer, scheme)
+    layer_weight_init: Vec<(usize, WeightInit)>,
+    seed: Option<u64>,
 }
 
-impl <'a> TrainerBuilder<'a> {
+impl <G: Genome> Default for TrainerBuilder<'_, G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <'a, G: Genome> TrainerBuilder<'a, G> {
 
     pub fn new() -> Self {
         Self { 
@@ -22,11 +52,23 @@ impl <'a> TrainerBuilder<'a> {
             crossover_rate: None,
             architecture: None,
             mutation_rate: None,
-            fitness_function: None
+            fitness_function: None,
+            fitness_scaling: FitnessScaling::None,
+            objective: Objective::Maximize,
+            mating_policy: MatingPolicy::Unrestricted,
+            crossover_operators: Vec::new(),
+            mutation_operators: Vec::new(),
+            operator_adaptation: OperatorAdaptation::Static,
+            speciation: None,
+            novelty: None,
+            genome_config: G::Config::default(),
+            weight_init: None,
+            layer_weight_init: Vec::new(),
+            seed: None,
         }
     }
 
-    pub fn build(&self) -> Result<EvoTrainer, TrainerBuildError> {
+    pub fn build(&self) -> Result<EvoTrainer<G>, TrainerBuildError> {
         let pop_size = self.population_size.ok_or(TrainerBuildError::VariableNotSet(String::from("population_size not set")))?;
         let arch = self.architecture.ok_or(TrainerBuildError::VariableNotSet(String::from("architecture not set")))?;
         let surv_rate = self.survival_rate.unwrap_or(0.0);
@@ -44,29 +86,163 @@ impl <'a> TrainerBuilder<'a> {
             }
         }
 
-        if surv_rate < 0.0 || surv_rate > 1.0 {
+        G::validate_config(arch, &self.genome_config).map_err(TrainerBuildError::ValidationError)?;
+
+        if !(0.0..=1.0).contains(&surv_rate) {
             return Err(TrainerBuildError::ValidationError(String::from("survival_rate must be between 0.0..=1.0")));
         }
 
-        if cross_rate < 0.0 || cross_rate > 1.0 {
+        if !(0.0..=1.0).contains(&cross_rate) {
             return Err(TrainerBuildError::ValidationError(String::from("crossover_rate must be between 0.0..=1.0")));
         }
 
-        if mut_rate < 0.0 || mut_rate > 1.0 {
+        if !(0.0..=1.0).contains(&mut_rate) {
             return Err(TrainerBuildError::ValidationError(String::from("mutation_rate must be between 0.0..=1.0")));
         }
 
-        if self.parent_strategies.len() == 0 {
+        match self.fitness_scaling {
+            FitnessScaling::Linear(multiplier) if multiplier <= 1.0 => {
+                return Err(TrainerBuildError::ValidationError(String::from("linear fitness_scaling multiplier must be greater than 1.0")));
+            },
+            FitnessScaling::SigmaTruncation(c) if c < 0.0 => {
+                return Err(TrainerBuildError::ValidationError(String::from("sigma truncation fitness_scaling c cannot be negative")));
+            },
+            FitnessScaling::PowerLaw(exponent) if exponent <= 0.0 => {
+                return Err(TrainerBuildError::ValidationError(String::from("power law fitness_scaling exponent must be greater than 0.0")));
+            },
+            _ => {}
+        }
+
+        match self.mating_policy {
+            MatingPolicy::PreferDissimilar(0) | MatingPolicy::PreferSimilar(0) => {
+                return Err(TrainerBuildError::ValidationError(String::from("mating_policy candidates must be greater than 0")));
+            },
+            MatingPolicy::IncestPrevention(0) => {
+                return Err(TrainerBuildError::ValidationError(String::from("mating_policy generations must be greater than 0")));
+            },
+            _ => {}
+        }
+
+        for strategy in self.parent_strategies.iter() {
+            if strategy.get_parent_count() < 2 {
+                return Err(TrainerBuildError::ValidationError(String::from("parent selection strategies need at least 2 parents")));
+            }
+        }
+
+        if self.parent_strategies.is_empty() {
             cross_rate = 0.0;
+        }
+
+        if let Some(config) = self.speciation {
+            if config.compatibility_threshold <= 0.0 {
+                return Err(TrainerBuildError::ValidationError(String::from("speciation compatibility_threshold must be greater than 0.0")));
+            }
+            if config.target_species > 0 && config.threshold_step <= 0.0 {
+                return Err(TrainerBuildError::ValidationError(String::from("speciation threshold_step must be greater than 0.0")));
+            }
+            if config.stagnation_limit == 0 {
+                return Err(TrainerBuildError::ValidationError(String::from("speciation stagnation_limit must be greater than 0")));
+            }
+        }
+
+        if let Some(config) = self.novelty {
+            if config.neighbours == 0 {
+                return Err(TrainerBuildError::ValidationError(String::from("novelty neighbours must be greater than 0")));
+            }
+            if config.archive_threshold < 0.0 {
+                return Err(TrainerBuildError::ValidationError(String::from("novelty archive_threshold cannot be negative")));
+            }
+            if !(0.0..=1.0).contains(&config.fitness_weight) {
+                return Err(TrainerBuildError::ValidationError(String::from("novelty fitness_weight must be between 0.0..=1.0")));
+            }
+        }
+
+        let mut cross_ops = self.crossover_operators.clone();
+        if cross_ops.is_empty() {
+            cross_ops.push((CrossoverOperator::default(), 1));
+        }
+
+        let mut mut_ops = self.mutation_operators.clone();
+        if mut_ops.is_empty() {
+            mut_ops.push((MutationOperator::default(), 1));
+        }
+
+        for (op, _) in mut_ops.iter() {
+            match op {
+                MutationOperator::Gaussian(size) | MutationOperator::Uniform(size) if *size <= 0.0 => {
+                    return Err(TrainerBuildError::ValidationError(String::from("mutation_operator size must be greater than 0.0")));
+                },
+                _ => {}
+            }
+        }
+
+        match self.operator_adaptation {
+            OperatorAdaptation::Static => {},
+            OperatorAdaptation::ProbabilityMatching(min_p, rate) | OperatorAdaptation::AdaptivePursuit(min_p, rate, _) => {
+                let most_operators = self.parent_strategies.len().max(cross_ops.len()).max(mut_ops.len()) as f64;
+                if min_p < 0.0 || min_p * most_operators >= 1.0 {
+                    return Err(TrainerBuildError::ValidationError(String::from("operator_adaptation min_probability must be between 0.0 and 1.0 / operator count")));
+                }
+                if rate <= 0.0 || rate > 1.0 {
+                    return Err(TrainerBuildError::ValidationError(String::from("operator_adaptation adaptation_rate must be between 0.0..=1.0 and greater than 0.0")));
+                }
+            }
+        }
+
+        if let OperatorAdaptation::AdaptivePursuit(_, _, learning_rate) = self.operator_adaptation {
+            if learning_rate <= 0.0 || learning_rate > 1.0 {
+                return Err(TrainerBuildError::ValidationError(String::from("operator_adaptation learning_rate must be between 0.0..=1.0 and greater than 0.0")));
+            }
         }        
 
+        let weight_layers = G::weight_layer_count(arch, &self.genome_config);
+        if weight_layers.is_none() && (self.weight_init.is_some() || !self.layer_weight_init.is_empty() || self.seed.is_some()) {
+            return Err(TrainerBuildError::ValidationError(String::from("weight_init and seed need a genome with layered weights")));
+        }
+
+        let mut weight_init = vec![self.weight_init.unwrap_or_default(); weight_layers.unwrap_or(0)];
+        for (layer, init) in self.layer_weight_init.iter() {
+            match weight_init.get_mut(*layer) {
+                Some(scheme) => *scheme = *init,
+                None => return Err(TrainerBuildError::ValidationError(String::from("weight_init layer must be below the number of weight layers"))),
+            }
+        }
+
+        for init in weight_init.iter() {
+            match *init {
+                WeightInit::Uniform(low, high) if low >= high => {
+                    return Err(TrainerBuildError::ValidationError(String::from("uniform weight_init low must be less than high")));
+                },
+                WeightInit::Gaussian(_, std_deviation) | WeightInit::Sparse(_, std_deviation) if std_deviation < 0.0 => {
+                    return Err(TrainerBuildError::ValidationError(String::from("weight_init std_deviation cannot be negative")));
+                },
+                WeightInit::Sparse(sparsity, _) if !(0.0..=1.0).contains(&sparsity) => {
+                    return Err(TrainerBuildError::ValidationError(String::from("sparse weight_init sparsity must be between 0.0..=1.0")));
+                },
+                _ => {}
+            }
+        }
+
         Ok(EvoTrainer::initialize(
             pop_size,
             arch,
             ft_fn,
-            surv_rate,
-            cross_rate,
-            mut_rate,
+            TrainerParams {
+                survival_rate: surv_rate,
+                crossover_rate: cross_rate,
+                mutation_rate: mut_rate,
+                fitness_scaling: self.fitness_scaling,
+                objective: self.objective,
+                mating_policy: self.mating_policy,
+                crossover_operators: cross_ops,
+                mutation_operators: mut_ops,
+                operator_adaptation: self.operator_adaptation,
+                speciation: self.speciation,
+                novelty: self.novelty,
+                genome_config: self.genome_config.clone(),
+                weight_init,
+                seed: self.seed,
+            },
             self.parent_strategies.clone()
         ))
     }
@@ -98,10 +274,94 @@ impl <'a> TrainerBuilder<'a> {
         self.mutation_rate = Some(rate);
     }
 
-    pub fn set_fitness_function(&mut self, fit_fn: fn(&mut EvoNet) -> f64) {
+    pub fn set_fitness_function(&mut self, fit_fn: fn(&mut G) -> f64) {
         self.fitness_function = Some(fit_fn);
     }
 
+    /// Transformation applied to the population fitness before
+    /// parent selection. Defaults to `FitnessScaling::None`
+    pub fn set_fitness_scaling(&mut self, scaling: FitnessScaling) {
+        self.fitness_scaling = scaling;
+    }
+
+    /// Whether the fitness function should be maximised or minimised.
+    /// Defaults to `Objective::Maximize`
+    pub fn set_objective(&mut self, objective: Objective) {
+        self.objective = objective;
+    }
+
+    /// Restriction on which parents may mate, applied on top of
+    /// every parent selection strategy. Defaults to `MatingPolicy::Unrestricted`
+    pub fn set_mating_policy(&mut self, policy: MatingPolicy) {
+        self.mating_policy = policy;
+    }
+
+    /// Adds an operator recombining the parents of each family into a child,
+    /// weighted against the other crossover operators.
+    /// Defaults to `CrossoverOperator::FitnessWeighted` when none are added
+    pub fn add_crossover_operator(&mut self, operator: CrossoverOperator, weight: usize) {
+        match self.crossover_operators.iter().position(|(op, _)| op == &operator) {
+            Some(i) => self.crossover_operators[i] = (operator, weight),
+            None => self.crossover_operators.push((operator, weight))
+        }
+    }
+
+    /// Adds an operator perturbing the weights picked by the mutation rate,
+    /// weighted against the other mutation operators.
+    /// Defaults to `MutationOperator::Gaussian(0.1)` when none are added
+    pub fn add_mutation_operator(&mut self, operator: MutationOperator, weight: usize) {
+        match self.mutation_operators.iter().position(|(op, _)| discriminant(op) == discriminant(&operator)) {
+            Some(i) => self.mutation_operators[i] = (operator, weight),
+            None => self.mutation_operators.push((operator, weight))
+        }
+    }
+
+    /// How the weights of the strategies and operators adapt during training.
+    /// Defaults to `OperatorAdaptation::Static`
+    pub fn set_operator_adaptation(&mut self, adaptation: OperatorAdaptation) {
+        self.operator_adaptation = adaptation;
+    }
+
+    /// Splits the population into species that reproduce separately
+    /// with explicit fitness sharing. Disabled by default
+    pub fn set_speciation(&mut self, config: SpeciationConfig) {
+        self.speciation = Some(config);
+    }
+
+    /// Selects on behavioural novelty, optionally blended with fitness.
+    /// Disabled by default
+    pub fn set_novelty_search(&mut self, config: NoveltyConfig<G>) {
+        self.novelty = Some(config);
+    }
+
+    /// Settings handed to every genome the trainer spawns.
+    /// Defaults to the genome's default config
+    pub fn set_genome_config(&mut self, config: G::Config) {
+        self.genome_config = config;
+    }
+
+    /// Scheme drawing the starting weights of every layer without its own.
+    /// Defaults to `WeightInit::Uniform(-1.0, 1.0)`
+    pub fn set_weight_init(&mut self, init: WeightInit) {
+        self.weight_init = Some(init);
+    }
+
+    /// Scheme drawing the starting weights of one weight layer of the genome,
+    /// 0 being the first hidden layer
+    pub fn set_layer_weight_init(&mut self, layer: usize, init: WeightInit) {
+        match self.layer_weight_init.iter().position(|(l, _)| *l == layer) {
+            Some(i) => self.layer_weight_init[i] = (layer, init),
+            None => self.layer_weight_init.push((layer, init))
+        }
+    }
+
+    /// Seeds the weight initialisation, making the first population reproducible.
+    /// Needs a genome with layered weights, see `Genome::weight_layer_count`.
+    /// Defaults to a seed from entropy
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
 }
 
 #[derive(Debug)]
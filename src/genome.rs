@@ -0,0 +1,57 @@
+use std::fmt::{Debug, Display};
+use rand::rngs::StdRng;
+use crate::evotrainer::{crossover::CrossoverOperator, evotrainer::HasFitness, initialization::WeightInit, mutation::MutationOperator, speciation::DistanceMetric};
+
+/// Anything a fitness function can feed inputs through. Writing fitness
+/// functions against this trait lets them evaluate every genome type
+pub trait Network {
+    fn calc(&mut self, x: &[f64]) -> &[f64];
+
+    /// Forgets the state carried between `calc` calls, the `EvoTrainer`
+    /// resets before every evaluation. Feedforward networks have none
+    fn reset_state(&mut self) {}
+}
+
+/// Encoding the `EvoTrainer` can spawn, recombine, mutate and compare
+pub trait Genome: Network + HasFitness + Clone + Display + Send {
+    /// Settings shared by every genome of a population, see `TrainerBuilder::set_genome_config`
+    type Config: Clone + Debug + Default + Send;
+
+    fn spawn(architecture: &[usize], config: &Self::Config) -> Self;
+
+    /// Checks the config against the trainer architecture when the trainer is built,
+    /// the message becomes a `TrainerBuildError::ValidationError`
+    fn validate_config(_architecture: &[usize], _config: &Self::Config) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Recombines any number of parents into a child, fitter parents
+    /// have the bigger fitness
+    fn from_many_parents(parents: &[&Self], fitnesses: &[f64], operator: CrossoverOperator) -> Self;
+
+    /// Perturbs the genome, each weight with probability `frequency` using the given operator
+    fn mutate_with(&mut self, frequency: f64, operator: MutationOperator);
+
+    fn distance_with(&self, other: &Self, metric: DistanceMetric) -> f64;
+
+    fn set_fitness(&mut self, ft: f64);
+
+    /// Shape of the genome
+    fn get_architecture(&self) -> Vec<usize>;
+
+    /// Whether the two genomes can meet in crossover, by default when they share the architecture
+    fn crossover_compatible(&self, other: &Self) -> bool {
+        self.get_architecture() == other.get_architecture()
+    }
+
+    /// Weight layers `initialize_weights` draws for the architecture and config.
+    /// `None` for genomes keeping their own initialisation, which rejects
+    /// weight init schemes and seeds when the trainer is built
+    fn weight_layer_count(_architecture: &[usize], _config: &Self::Config) -> Option<usize> {
+        None
+    }
+
+    /// Redraws the starting weights, `schemes[i]` for the i-th weight layer.
+    /// Genomes without layered weights keep their own initialisation
+    fn initialize_weights(&mut self, _schemes: &[WeightInit], _rng: &mut StdRng) {}
+}
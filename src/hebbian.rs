@@ -0,0 +1,227 @@
+use std::fmt::Display;
+use rand::{rngs::StdRng, Rng};
+
+use crate::{evonet::EvoNet, evotrainer::{crossover::CrossoverOperator, evotrainer::HasFitness, initialization::WeightInit, mutation::MutationOperator, speciation::DistanceMetric}, genome::{Genome, Network}};
+
+/// Coefficients of one rule: learning rate, then A, B, C and D
+const RULE_SIZE: usize = 5;
+
+/// Which connections share a rule
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RuleGranularity {
+    /// Every connection, biases included, evolves its own rule
+    #[default]
+    PerConnection,
+    /// All connections of a layer share one rule
+    PerLayer,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HebbianConfig {
+    pub granularity: RuleGranularity,
+    /// Weights are clamped to [-limit, limit] after every update
+    pub weight_limit: f64,
+}
+
+impl Default for HebbianConfig {
+    fn default() -> Self {
+        HebbianConfig {
+            granularity: RuleGranularity::PerConnection,
+            weight_limit: 1.0,
+        }
+    }
+}
+
+/// `EvoNet` whose weights learn during an episode with the ABCD rule
+/// `dw = eta * (A * pre * post + B * pre + C * post + D)`, biases see a
+/// constant pre of 1. Only the rules evolve, the starting weights are drawn
+/// at spawn, inherited from the fittest parent and restored by `reset_state`
+#[derive(Clone)]
+pub struct HebbianNet {
+    net: EvoNet,
+    initial_weights: Vec<f64>,
+    rules: Vec<f64>,
+    config: HebbianConfig,
+    fitness: f64,
+}
+
+impl HebbianNet {
+    pub fn new(architecture: &[usize], config: &HebbianConfig) -> HebbianNet {
+        let net = EvoNet::new(architecture);
+        let rule_count = match config.granularity {
+            RuleGranularity::PerConnection => net.weight_count(),
+            RuleGranularity::PerLayer => architecture.len() - 1,
+        };
+        let rules = (0..rule_count * RULE_SIZE).map(|i| {
+            if i % RULE_SIZE == 0 {
+                0.1 * rand::random::<f64>()
+            } else {
+                2f64 * rand::random::<f64>() - 1f64
+            }
+        }).collect();
+
+        HebbianNet {
+            initial_weights: net.get_weights(),
+            net,
+            rules,
+            config: *config,
+            fitness: 0.0,
+        }
+    }
+
+    /// Recombines the rules of parents with the same architecture and granularity,
+    /// the child starts from the weights of the fittest parent
+    pub fn from_many_parents(parents: &[&HebbianNet], fitnesses: &[f64], operator: CrossoverOperator) -> HebbianNet {
+        let fittest = (0..parents.len()).fold(0, |best, i| if fitnesses[i] > fitnesses[best] { i } else { best });
+        let rules: Vec<Vec<f64>> = parents.iter().map(|p| p.rules.clone()).collect();
+        let mut nn = parents[fittest].clone();
+        nn.rules = operator.recombine(&rules, fitnesses);
+        nn.fitness = 0.0;
+        nn.reset_state();
+        nn
+    }
+
+    pub fn get_architecture(&self) -> Vec<usize> {
+        self.net.get_architecture()
+    }
+
+    /// Flat rule coefficients, `eta, A, B, C, D` per rule
+    pub fn get_rules(&self) -> &[f64] {
+        &self.rules
+    }
+
+    pub fn set_rules(&mut self, rules: &[f64]) {
+        self.rules.copy_from_slice(rules);
+    }
+
+    /// Weights as learned so far in the episode, in the order of `EvoNet::get_weights`
+    pub fn get_weights(&self) -> Vec<f64> {
+        self.net.get_weights()
+    }
+
+    /// The net with its current learned weights
+    pub fn get_net(&self) -> &EvoNet {
+        &self.net
+    }
+
+    pub fn distance_with(&self, other: &HebbianNet, metric: DistanceMetric) -> f64 {
+        metric.measure(&self.rules, &other.rules)
+    }
+
+    pub fn set_fitness(&mut self, ft: f64) {
+        self.fitness = ft;
+    }
+
+    /// Perturbs each rule coefficient with probability `frequency` using the given operator
+    pub fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        operator.mutate(&mut self.rules, frequency);
+    }
+
+    /// Redraws the starting weights of the net layer by layer, see `EvoNet::initialize_weights`
+    pub fn initialize_weights<R: Rng>(&mut self, schemes: &[WeightInit], rng: &mut R) {
+        self.net.initialize_weights(schemes, rng);
+        self.initial_weights = self.net.get_weights();
+    }
+
+    /// Restores the starting weights, forgetting everything learned
+    pub fn reset_state(&mut self) {
+        self.net.set_weights(&self.initial_weights);
+    }
+
+    /// Feeds the input forward, then updates every weight with its rule
+    pub fn calc(&mut self, x: &[f64]) -> &[f64] {
+        self.net.calc(x);
+        self.learn(x);
+        self.net.layer_outputs().pop().unwrap()
+    }
+
+    fn learn(&mut self, x: &[f64]) {
+        let mut weights = self.net.get_weights();
+        let outputs = self.net.layer_outputs();
+        let limit = self.config.weight_limit;
+        let mut position: usize = 0;
+
+        for (layer, post) in outputs.iter().enumerate() {
+            let pre = if layer == 0 { x } else { outputs[layer - 1] };
+            for post_i in post.iter() {
+                for k in 0..pre.len() + 1 {
+                    let rule_index = match self.config.granularity {
+                        RuleGranularity::PerConnection => position,
+                        RuleGranularity::PerLayer => layer,
+                    };
+                    let rule = &self.rules[rule_index * RULE_SIZE..(rule_index + 1) * RULE_SIZE];
+                    let pre_k = if k == 0 { 1.0 } else { pre[k - 1] };
+                    let dw = rule[0] * (rule[1] * pre_k * post_i + rule[2] * pre_k + rule[3] * post_i + rule[4]);
+                    weights[position] = (weights[position] + dw).clamp(-limit, limit);
+                    position += 1;
+                }
+            }
+        }
+
+        self.net.set_weights(&weights);
+    }
+}
+
+impl Display for HebbianNet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut rules_str = String::from("");
+        self.rules.chunks(RULE_SIZE).for_each(|r| {
+            let coefficients: Vec<String> = r.iter().map(|c| c.to_string()).collect();
+            rules_str.push_str(&format!("[{}]\n", coefficients.join(", ")));
+        });
+
+        write!(f, "fitness: {}\ngranularity: {:?}\n{}", self.fitness, self.config.granularity, rules_str)
+    }
+}
+
+impl HasFitness for HebbianNet {
+    fn get_fitness(&self) -> f64 {
+        self.fitness
+    }
+}
+
+impl Network for HebbianNet {
+    fn calc(&mut self, x: &[f64]) -> &[f64] {
+        HebbianNet::calc(self, x)
+    }
+
+    fn reset_state(&mut self) {
+        HebbianNet::reset_state(self)
+    }
+}
+
+impl Genome for HebbianNet {
+    type Config = HebbianConfig;
+
+    fn spawn(architecture: &[usize], config: &HebbianConfig) -> Self {
+        HebbianNet::new(architecture, config)
+    }
+
+    fn from_many_parents(parents: &[&Self], fitnesses: &[f64], operator: CrossoverOperator) -> Self {
+        HebbianNet::from_many_parents(parents, fitnesses, operator)
+    }
+
+    fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        HebbianNet::mutate_with(self, frequency, operator)
+    }
+
+    fn distance_with(&self, other: &Self, metric: DistanceMetric) -> f64 {
+        HebbianNet::distance_with(self, other, metric)
+    }
+
+    fn set_fitness(&mut self, ft: f64) {
+        HebbianNet::set_fitness(self, ft)
+    }
+
+    fn get_architecture(&self) -> Vec<usize> {
+        HebbianNet::get_architecture(self)
+    }
+
+    fn weight_layer_count(architecture: &[usize], _config: &HebbianConfig) -> Option<usize> {
+        Some(architecture.len().saturating_sub(1))
+    }
+
+    fn initialize_weights(&mut self, schemes: &[WeightInit], rng: &mut StdRng) {
+        HebbianNet::initialize_weights(self, schemes, rng)
+    }
+}
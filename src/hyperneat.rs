@@ -0,0 +1,248 @@
+use std::fmt::Display;
+
+use crate::{activators, evonet::EvoNet, evotrainer::{crossover::CrossoverOperator, evotrainer::HasFitness, mutation::MutationOperator, speciation::DistanceMetric}, genome::{Genome, Network}, neat::{NeatConfig, NeatGenome}};
+
+/// Inputs of the CPPN: source x and y, target x and y, target layer depth
+const CPPN_INPUTS: usize = 5;
+/// Outputs of the CPPN: connection weight, target bias
+const CPPN_OUTPUTS: usize = 2;
+/// Activations hidden CPPN nodes draw from by default
+pub const CPPN_ACTIVATIONS: [activators::Type; 5] = [
+    activators::Type::Sine,
+    activators::Type::Gaussian,
+    activators::Type::Sigmoid,
+    activators::Type::Tanh,
+    activators::Type::Identity,
+];
+
+/// Positions in [-1, 1]^2 of the nodes of every layer, starting with the inputs
+#[derive(Clone, Debug, PartialEq)]
+pub struct Substrate {
+    pub layers: Vec<Vec<(f64, f64)>>,
+}
+
+impl Substrate {
+    pub fn new(layers: Vec<Vec<(f64, f64)>>) -> Substrate {
+        Substrate { layers }
+    }
+
+    /// Each layer a `(width, height)` grid spread evenly over the plane
+    pub fn grid(shapes: &[(usize, usize)]) -> Substrate {
+        let layers = shapes.iter().map(|(width, height)| {
+            (0..*height).flat_map(|y| (0..*width).map(move |x| (spread(x, *width), spread(y, *height)))).collect()
+        }).collect();
+        Substrate { layers }
+    }
+
+    /// Each layer a single row, as used for the plain trainer architecture
+    pub fn rows(architecture: &[usize]) -> Substrate {
+        let shapes: Vec<(usize, usize)> = architecture.iter().map(|n| (*n, 1)).collect();
+        Self::grid(&shapes)
+    }
+
+    /// Node count of each layer, starting with the inputs
+    pub fn get_architecture(&self) -> Vec<usize> {
+        self.layers.iter().map(|l| l.len()).collect()
+    }
+}
+
+/// Coordinate of the `index`th of `count` evenly spaced points in [-1, 1]
+fn spread(index: usize, count: usize) -> f64 {
+    if count < 2 {
+        0.0
+    } else {
+        2.0 * index as f64 / (count - 1) as f64 - 1.0
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HyperNeatConfig {
+    /// Node positions, one layer per entry of the trainer architecture and one node
+    /// per neuron. `None` lays each layer of the architecture out as a row
+    pub substrate: Option<Substrate>,
+    /// Settings of the evolved CPPNs, by default hidden nodes draw from
+    /// `CPPN_ACTIVATIONS` to express symmetric and repeating patterns
+    pub cppn: NeatConfig,
+    /// CPPN outputs with a smaller magnitude leave the connection at zero
+    pub weight_threshold: f64,
+    /// Magnitude the strongest CPPN output maps to
+    pub max_weight: f64,
+}
+
+impl Default for HyperNeatConfig {
+    fn default() -> Self {
+        HyperNeatConfig {
+            substrate: None,
+            cppn: NeatConfig {
+                activations: CPPN_ACTIVATIONS.to_vec(),
+                activation_mutation_rate: 0.05,
+                ..Default::default()
+            },
+            weight_threshold: 0.2,
+            max_weight: 3.0,
+        }
+    }
+}
+
+/// Indirect encoding, a NEAT evolved CPPN queried with the substrate coordinates
+/// of both ends of every connection produces the weights of an `EvoNet`.
+/// Biases are queried with the source at the origin
+#[derive(Clone)]
+pub struct HyperNeatGenome {
+    cppn: NeatGenome,
+    substrate: Substrate,
+    weight_threshold: f64,
+    max_weight: f64,
+    net: EvoNet,
+    fitness: f64,
+}
+
+impl HyperNeatGenome {
+    pub fn new(architecture: &[usize], config: &HyperNeatConfig) -> HyperNeatGenome {
+        let substrate = config.substrate.clone().unwrap_or_else(|| Substrate::rows(architecture));
+        let mut genome = HyperNeatGenome {
+            cppn: NeatGenome::new(&[CPPN_INPUTS, CPPN_OUTPUTS], &config.cppn),
+            net: EvoNet::new(&substrate.get_architecture()),
+            substrate,
+            weight_threshold: config.weight_threshold,
+            max_weight: config.max_weight,
+            fitness: 0.0,
+        };
+        genome.decode();
+        genome
+    }
+
+    /// Recombines the CPPNs of parents sharing a substrate
+    pub fn from_many_parents(parents: &[&HyperNeatGenome], fitnesses: &[f64], operator: CrossoverOperator) -> HyperNeatGenome {
+        let cppns: Vec<&NeatGenome> = parents.iter().map(|p| &p.cppn).collect();
+        let mut genome = parents[0].clone();
+        genome.cppn = NeatGenome::from_many_parents(&cppns, fitnesses, operator);
+        genome.fitness = 0.0;
+        genome.decode();
+        genome
+    }
+
+    pub fn get_cppn(&self) -> &NeatGenome {
+        &self.cppn
+    }
+
+    pub fn get_substrate(&self) -> &Substrate {
+        &self.substrate
+    }
+
+    pub fn get_architecture(&self) -> Vec<usize> {
+        self.substrate.get_architecture()
+    }
+
+    /// The decoded network, for inference without the CPPN
+    pub fn to_evonet(&self) -> EvoNet {
+        let mut net = self.net.clone();
+        net.set_fitness(self.fitness);
+        net
+    }
+
+    /// Compatibility distance of the CPPNs
+    pub fn distance_with(&self, other: &HyperNeatGenome, metric: DistanceMetric) -> f64 {
+        self.cppn.distance_with(&other.cppn, metric)
+    }
+
+    pub fn set_fitness(&mut self, ft: f64) {
+        self.fitness = ft;
+    }
+
+    /// Mutates the CPPN and decodes the substrate weights again
+    pub fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        self.cppn.mutate_with(frequency, operator);
+        self.decode();
+    }
+
+    pub fn calc(&mut self, x: &[f64]) -> &[f64] {
+        self.net.calc(x)
+    }
+
+    /// Queries the CPPN for every bias and weight of the net
+    fn decode(&mut self) {
+        let mut weights = Vec::with_capacity(self.net.weight_count());
+        let depth = self.substrate.layers.len();
+
+        for layer in 1..depth {
+            let z = spread(layer, depth);
+            for &(x2, y2) in self.substrate.layers[layer].iter() {
+                let bias = self.cppn.calc(&[0.0, 0.0, x2, y2, z])[1];
+                weights.push(self.scale(bias));
+                for &(x1, y1) in self.substrate.layers[layer - 1].iter() {
+                    let weight = self.cppn.calc(&[x1, y1, x2, y2, z])[0];
+                    weights.push(self.scale(weight));
+                }
+            }
+        }
+
+        self.net.set_weights(&weights);
+    }
+
+    /// Squashes a CPPN output into [-1, 1], zeroes it below the threshold
+    /// and stretches the rest to the weight range
+    fn scale(&self, output: f64) -> f64 {
+        let squashed = output.tanh();
+        if squashed.abs() < self.weight_threshold {
+            return 0.0;
+        }
+
+        squashed.signum() * (squashed.abs() - self.weight_threshold) / (1.0 - self.weight_threshold) * self.max_weight
+    }
+}
+
+impl Display for HyperNeatGenome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "fitness: {}\nsubstrate: {:?}\ncppn {}", self.fitness, self.substrate.get_architecture(), self.cppn)
+    }
+}
+
+impl HasFitness for HyperNeatGenome {
+    fn get_fitness(&self) -> f64 {
+        self.fitness
+    }
+}
+
+impl Network for HyperNeatGenome {
+    fn calc(&mut self, x: &[f64]) -> &[f64] {
+        HyperNeatGenome::calc(self, x)
+    }
+}
+
+impl Genome for HyperNeatGenome {
+    type Config = HyperNeatConfig;
+
+    fn spawn(architecture: &[usize], config: &HyperNeatConfig) -> Self {
+        HyperNeatGenome::new(architecture, config)
+    }
+
+    fn validate_config(architecture: &[usize], config: &HyperNeatConfig) -> Result<(), String> {
+        match &config.substrate {
+            Some(substrate) if substrate.get_architecture() != architecture => {
+                Err(String::from("hyperneat substrate must have a node per neuron of every layer of the architecture"))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn from_many_parents(parents: &[&Self], fitnesses: &[f64], operator: CrossoverOperator) -> Self {
+        HyperNeatGenome::from_many_parents(parents, fitnesses, operator)
+    }
+
+    fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        HyperNeatGenome::mutate_with(self, frequency, operator)
+    }
+
+    fn distance_with(&self, other: &Self, metric: DistanceMetric) -> f64 {
+        HyperNeatGenome::distance_with(self, other, metric)
+    }
+
+    fn set_fitness(&mut self, ft: f64) {
+        HyperNeatGenome::set_fitness(self, ft)
+    }
+
+    fn get_architecture(&self) -> Vec<usize> {
+        HyperNeatGenome::get_architecture(self)
+    }
+}
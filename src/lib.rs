@@ -1,3 +1,10 @@
 pub mod activators;
+pub mod convnet;
+pub mod ctrnn;
 pub mod evonet;
-pub mod evotrainer;
\ No newline at end of file
+pub mod evotrainer;
+pub mod genome;
+pub mod hebbian;
+pub mod hyperneat;
+pub mod neat;
+pub mod recurrent;
@@ -1,4 +1,4 @@
-use evoflow::{evotrainer::{trainer_builder::TrainerBuilder, crossover::{PrimeParentStrategy, Strategies}}, evonet::EvoNet};
+use evoflow::{evotrainer::{trainer_builder::TrainerBuilder, crossover::{PrimeParentStrategy, Strategies}}, evonet::EvoNet, genome::Network};
 
 fn main() {
     // let params = TrainerParams::build(
@@ -14,13 +14,14 @@ fn main() {
     //     .set_population_size(1000)
     //     .build()
     //     .unwrap_or_else(|e| panic!("{}", e));
-    let mut builder = TrainerBuilder::new();
+    let mut builder = TrainerBuilder::<EvoNet>::new();
     builder.set_architecture(&[2, 2, 1]);
     builder.set_population_size(1000);
     builder.set_fitness_function(xor_fit_fn);
     builder.add_parent_selection_strategy(Strategies::PrimeParent(PrimeParentStrategy {
         weight: 1,
         rate: 0.1,
+        parents: 2,
     }));
 
     let mut trainer = builder.build().unwrap_or_else(|e| panic!("{}", e));
@@ -76,7 +77,7 @@ fn main() {
 
 }
 
-fn xor_fit_fn(net: &mut EvoNet) -> f64 {   
+fn xor_fit_fn<N: Network>(net: &mut N) -> f64 {   
     let a0 = net.calc(&[0.0, 0.0])[0]; // Should be 0
     let a1 = net.calc(&[0.0, 1.0])[0]; // Should be 1
     let a2 = net.calc(&[1.0, 0.0])[0]; // Should be 1
@@ -98,7 +99,7 @@ fn xor_fit_fn(net: &mut EvoNet) -> f64 {
     fit0 + fit1 + fit2 + fit3
 }
 
-fn xor_fit_fn_print(net: &mut EvoNet){   
+fn xor_fit_fn_print<N: Network>(net: &mut N){   
     let a0 = net.calc(&[0.0, 0.0])[0]; // Should be 0
     let a1 = net.calc(&[0.0, 1.0])[0]; // Should be 1
     let a2 = net.calc(&[1.0, 0.0])[0]; // Should be 1
@@ -0,0 +1,615 @@
+use std::{collections::HashMap, fmt::Display, sync::{Arc, Mutex}};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+
+use crate::{activators, evotrainer::{crossover::{parent_shares, CrossoverOperator}, evotrainer::HasFitness, mutation::MutationOperator, speciation::DistanceMetric}, genome::{Genome, Network}};
+
+/// Attempts at finding a new acyclic connection before add-connection gives up
+const MAX_CONNECTION_ATTEMPTS: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeKind {
+    Input,
+    /// Always outputs 1.0
+    Bias,
+    Hidden,
+    Output,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeGene {
+    pub id: usize,
+    pub kind: NodeKind,
+    /// Applied to the weighted sum of a hidden node. Inputs, bias and outputs keep `Identity`
+    pub activation: activators::Type,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConnectionGene {
+    pub innovation: usize,
+    pub from: usize,
+    pub to: usize,
+    pub weight: f64,
+    pub enabled: bool,
+}
+
+/// Hands out innovation numbers and node ids. The same structural
+/// mutation always receives the same number within one tracker, so
+/// genomes sharing a tracker align their genes in crossover
+#[derive(Debug, Default)]
+pub struct InnovationTracker {
+    connections: HashMap<(usize, usize), usize>,
+    /// Node created by splitting the connection with this innovation
+    splits: HashMap<usize, usize>,
+    next_innovation: usize,
+    next_node: usize,
+}
+
+impl InnovationTracker {
+    fn connection(&mut self, from: usize, to: usize) -> usize {
+        let next = &mut self.next_innovation;
+        *self.connections.entry((from, to)).or_insert_with(|| {
+            *next += 1;
+            *next - 1
+        })
+    }
+
+    fn split(&mut self, innovation: usize) -> usize {
+        let next = &mut self.next_node;
+        *self.splits.entry(innovation).or_insert_with(|| {
+            *next += 1;
+            *next - 1
+        })
+    }
+}
+
+/// Structural mutation rates and compatibility coefficients of a NEAT population
+#[derive(Clone, Debug)]
+pub struct NeatConfig {
+    /// Chance per mutation to split a connection with a new node
+    pub add_node_rate: f64,
+    /// Chance per mutation to connect two unconnected nodes
+    pub add_connection_rate: f64,
+    /// Chance per mutation to enable or disable a random connection
+    pub toggle_rate: f64,
+    /// Chance a gene disabled in either parent stays disabled in the child
+    pub inherit_disabled_rate: f64,
+    /// Activations new hidden nodes draw from
+    pub activations: Vec<activators::Type>,
+    /// Chance per hidden node and mutation of drawing a new activation from `activations`
+    pub activation_mutation_rate: f64,
+    /// c1 of the compatibility distance
+    pub excess_coefficient: f64,
+    /// c2 of the compatibility distance
+    pub disjoint_coefficient: f64,
+    /// c3 of the compatibility distance
+    pub weight_coefficient: f64,
+    /// Shared by every genome spawned from this config
+    pub innovations: Arc<Mutex<InnovationTracker>>,
+}
+
+impl Default for NeatConfig {
+    fn default() -> Self {
+        Self {
+            add_node_rate: 0.03,
+            add_connection_rate: 0.05,
+            toggle_rate: 0.01,
+            inherit_disabled_rate: 0.75,
+            activations: vec![activators::Type::Tanh],
+            activation_mutation_rate: 0.0,
+            excess_coefficient: 1.0,
+            disjoint_coefficient: 1.0,
+            weight_coefficient: 0.4,
+            innovations: Arc::new(Mutex::new(InnovationTracker::default())),
+        }
+    }
+}
+
+/// Topology evolving genome of node and connection genes. Starts with every
+/// input and the bias connected to every output, hidden nodes draw their
+/// activation from the config and outputs are linear like the layers of `EvoNet`
+#[derive(Clone)]
+pub struct NeatGenome {
+    /// Sorted by id
+    nodes: Vec<NodeGene>,
+    /// Sorted by innovation
+    connections: Vec<ConnectionGene>,
+    inputs: usize,
+    outputs: usize,
+    config: NeatConfig,
+    values: Vec<f64>,
+    output_values: Vec<f64>,
+    /// Cached evaluation order, cleared whenever the enabled structure changes
+    order: Option<Vec<usize>>,
+    fitness: f64,
+}
+
+impl NeatGenome {
+    /// Minimal genome for the first and last entry of the architecture,
+    /// hidden layers are left to evolve
+    pub fn new(architecture: &[usize], config: &NeatConfig) -> NeatGenome {
+        let inputs = architecture[0];
+        let outputs = architecture[architecture.len() - 1];
+        let mut rng = thread_rng();
+        let mut tracker = config.innovations.lock().unwrap();
+        tracker.next_node = tracker.next_node.max(inputs + 1 + outputs);
+
+        let nodes: Vec<NodeGene> = (0..inputs + 1 + outputs).map(|id| NodeGene {
+            id,
+            kind: if id < inputs {
+                NodeKind::Input
+            } else if id == inputs {
+                NodeKind::Bias
+            } else {
+                NodeKind::Output
+            },
+            activation: activators::Type::Identity,
+        }).collect();
+
+        let mut connections = Vec::with_capacity((inputs + 1) * outputs);
+        for to in inputs + 1..inputs + 1 + outputs {
+            for from in 0..=inputs {
+                connections.push(ConnectionGene {
+                    innovation: tracker.connection(from, to),
+                    from,
+                    to,
+                    weight: rng.gen_range(-1.0..=1.0),
+                    enabled: true,
+                });
+            }
+        }
+        connections.sort_by_key(|c| c.innovation);
+        drop(tracker);
+
+        NeatGenome {
+            values: vec![0.0; nodes.len()],
+            output_values: vec![0.0; outputs],
+            nodes,
+            connections,
+            inputs,
+            outputs,
+            config: config.clone(),
+            order: None,
+            fitness: 0.0,
+        }
+    }
+
+    pub fn get_nodes(&self) -> &[NodeGene] {
+        &self.nodes
+    }
+
+    pub fn get_connections(&self) -> &[ConnectionGene] {
+        &self.connections
+    }
+
+    pub fn set_fitness(&mut self, ft: f64) {
+        self.fitness = ft;
+    }
+
+    /// Aligns the connection genes by innovation number. Genes the fittest
+    /// parent lacks are dropped, matching genes are recombined by the operator.
+    /// Hidden nodes inherit the activation of a parent carrying them, drawn by fitness
+    /// or uniformly for `CrossoverOperator::Uniform`
+    pub fn from_many_parents(parents: &[&NeatGenome], fitnesses: &[f64], operator: CrossoverOperator) -> NeatGenome {
+        let mut rng = thread_rng();
+        let fittest = (0..parents.len()).reduce(|best, p| if fitnesses[p] > fitnesses[best] { p } else { best }).unwrap();
+        let mut child = parents[fittest].clone();
+        child.fitness = 0.0;
+
+        let parent_weights = parent_shares(fitnesses);
+
+        let mut cuts: Vec<usize> = match operator {
+            CrossoverOperator::Diagonal => (1..parents.len()).map(|_| rng.gen_range(0..=child.connections.len())).collect(),
+            _ => Vec::new(),
+        };
+        cuts.sort_unstable();
+
+        for (position, gene) in child.connections.iter_mut().enumerate() {
+            // (parent, matching gene) of every parent carrying the innovation
+            let matching: Vec<(usize, &ConnectionGene)> = parents.iter().enumerate()
+                .filter_map(|(p, parent)| parent.find_connection(gene.innovation).map(|c| (p, c)))
+                .collect();
+
+            gene.weight = match operator {
+                CrossoverOperator::FitnessWeighted => {
+                    let total = matching.iter().fold(0.0, |sum, (p, _)| sum + parent_weights[*p]);
+                    let mut ball = rng.gen_range(0.0..1.0) * total;
+                    let mut weight = matching[matching.len() - 1].1.weight;
+                    for (p, c) in matching.iter() {
+                        ball -= parent_weights[*p];
+                        if ball < 0.0 {
+                            weight = c.weight;
+                            break;
+                        }
+                    }
+                    weight
+                },
+                CrossoverOperator::Uniform => matching.choose(&mut rng).unwrap().1.weight,
+                CrossoverOperator::Centroid => {
+                    let total = matching.iter().fold(0.0, |sum, (p, _)| sum + parent_weights[*p]);
+                    if total > 0.0 {
+                        matching.iter().fold(0.0, |sum, (p, c)| sum + parent_weights[*p] * c.weight) / total
+                    } else {
+                        matching.iter().fold(0.0, |sum, (_, c)| sum + c.weight) / matching.len() as f64
+                    }
+                },
+                CrossoverOperator::Diagonal => {
+                    let p = cuts.partition_point(|cut| *cut <= position);
+                    matching.iter().find(|(m, _)| *m == p).map(|(_, c)| c.weight).unwrap_or(gene.weight)
+                },
+            };
+
+            if matching.iter().any(|(_, c)| !c.enabled) {
+                gene.enabled = rng.gen_range(0.0..1.0) >= child.config.inherit_disabled_rate;
+            }
+        }
+        child.order = None;
+
+        for node in child.nodes.iter_mut().filter(|n| n.kind == NodeKind::Hidden) {
+            let carriers: Vec<(usize, activators::Type)> = parents.iter().enumerate()
+                .filter_map(|(p, parent)| parent.find_node(node.id).map(|i| (p, parent.nodes[i].activation)))
+                .collect();
+            node.activation = match operator {
+                CrossoverOperator::Uniform => carriers.choose(&mut rng).unwrap().1,
+                _ => {
+                    let total = carriers.iter().fold(0.0, |sum, (p, _)| sum + parent_weights[*p]);
+                    let mut ball = rng.gen_range(0.0..1.0) * total;
+                    let mut activation = carriers[carriers.len() - 1].1;
+                    for (p, a) in carriers.iter() {
+                        ball -= parent_weights[*p];
+                        if ball < 0.0 {
+                            activation = *a;
+                            break;
+                        }
+                    }
+                    activation
+                },
+            };
+        }
+
+        child
+    }
+
+    /// Perturbs each weight with probability `frequency` using the given operator,
+    /// then redraws activations and applies the structural mutations at the rates of the config
+    pub fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        let mut rng = thread_rng();
+        for gene in self.connections.iter_mut() {
+            if rng.gen_range(0.0..=1.0) <= frequency {
+                gene.weight = operator.perturb(gene.weight, &mut rng);
+            }
+        }
+
+        for node in self.nodes.iter_mut().filter(|n| n.kind == NodeKind::Hidden) {
+            if rng.gen_range(0.0..1.0) < self.config.activation_mutation_rate {
+                node.activation = *self.config.activations.choose(&mut rng).unwrap_or(&activators::Type::Tanh);
+            }
+        }
+
+        if rng.gen_range(0.0..1.0) < self.config.add_node_rate {
+            self.add_node();
+        }
+        if rng.gen_range(0.0..1.0) < self.config.add_connection_rate {
+            self.add_connection();
+        }
+        if rng.gen_range(0.0..1.0) < self.config.toggle_rate {
+            self.toggle_connection();
+        }
+    }
+
+    /// Splits a random enabled connection, the incoming connection gets
+    /// weight 1.0 and the outgoing one the old weight
+    pub fn add_node(&mut self) {
+        let mut rng = thread_rng();
+        let enabled: Vec<usize> = (0..self.connections.len()).filter(|i| self.connections[*i].enabled).collect();
+        let Some(&split) = enabled.choose(&mut rng) else {
+            return;
+        };
+
+        let old = self.connections[split];
+        let mut tracker = self.config.innovations.lock().unwrap();
+        let node = tracker.split(old.innovation);
+        if self.find_node(node).is_some() {
+            // Already split in this genome, the gene has been re-enabled since
+            return;
+        }
+        let incoming = tracker.connection(old.from, node);
+        let outgoing = tracker.connection(node, old.to);
+        drop(tracker);
+
+        let activation = *self.config.activations.choose(&mut rng).unwrap_or(&activators::Type::Tanh);
+        self.connections[split].enabled = false;
+        self.insert_node(NodeGene { id: node, kind: NodeKind::Hidden, activation });
+        self.insert_connection(ConnectionGene { innovation: incoming, from: old.from, to: node, weight: 1.0, enabled: true });
+        self.insert_connection(ConnectionGene { innovation: outgoing, from: node, to: old.to, weight: old.weight, enabled: true });
+    }
+
+    /// Connects two unconnected nodes with a random weight, never creating a cycle
+    pub fn add_connection(&mut self) {
+        let mut rng = thread_rng();
+        for _ in 0..MAX_CONNECTION_ATTEMPTS {
+            let from = self.nodes.choose(&mut rng).unwrap();
+            let to = self.nodes.choose(&mut rng).unwrap();
+            if from.kind == NodeKind::Output || matches!(to.kind, NodeKind::Input | NodeKind::Bias) || from.id == to.id {
+                continue;
+            }
+            if self.connections.iter().any(|c| c.from == from.id && c.to == to.id) || self.reaches(to.id, from.id) {
+                continue;
+            }
+
+            let (from, to) = (from.id, to.id);
+            let innovation = self.config.innovations.lock().unwrap().connection(from, to);
+            self.insert_connection(ConnectionGene { innovation, from, to, weight: rng.gen_range(-1.0..=1.0), enabled: true });
+            return;
+        }
+    }
+
+    /// Flips a random connection between enabled and disabled
+    pub fn toggle_connection(&mut self) {
+        let mut rng = thread_rng();
+        if let Some(gene) = self.connections.choose_mut(&mut rng) {
+            gene.enabled = !gene.enabled;
+            self.order = None;
+        }
+    }
+
+    /// NEAT compatibility distance c1 * E / N + c2 * D / N + c3 * W, where W is
+    /// the metric between the weights of matching genes divided by their count
+    pub fn distance_with(&self, other: &NeatGenome, metric: DistanceMetric) -> f64 {
+        let (mut excess, mut disjoint) = (0, 0);
+        let (mut own_weights, mut other_weights) = (Vec::new(), Vec::new());
+        let own_max = self.connections.last().map(|c| c.innovation).unwrap_or(0);
+        let other_max = other.connections.last().map(|c| c.innovation).unwrap_or(0);
+
+        let (mut i, mut j) = (0, 0);
+        while i < self.connections.len() || j < other.connections.len() {
+            let a = self.connections.get(i);
+            let b = other.connections.get(j);
+            match (a, b) {
+                (Some(a), Some(b)) if a.innovation == b.innovation => {
+                    own_weights.push(a.weight);
+                    other_weights.push(b.weight);
+                    i += 1;
+                    j += 1;
+                },
+                (Some(a), b) if b.is_none_or(|b| a.innovation < b.innovation) => {
+                    if a.innovation > other_max { excess += 1 } else { disjoint += 1 }
+                    i += 1;
+                },
+                (_, Some(b)) => {
+                    if b.innovation > own_max { excess += 1 } else { disjoint += 1 }
+                    j += 1;
+                },
+                _ => break,
+            }
+        }
+
+        let genes = self.connections.len().max(other.connections.len()).max(1) as f64;
+        let weight_difference = if own_weights.is_empty() {
+            0.0
+        } else {
+            metric.measure(&own_weights, &other_weights) / own_weights.len() as f64
+        };
+
+        self.config.excess_coefficient * excess as f64 / genes
+            + self.config.disjoint_coefficient * disjoint as f64 / genes
+            + self.config.weight_coefficient * weight_difference
+    }
+
+    /// Missing inputs are read as 0.0 and extra inputs are ignored
+    #[allow(non_snake_case)]
+    pub fn calc(&mut self, X: &[f64]) -> &[f64] {
+        self.values.iter_mut().for_each(|v| *v = 0.0);
+        let given = X.len().min(self.inputs);
+        self.values[..given].copy_from_slice(&X[..given]);
+        self.values[self.inputs] = 1.0;
+
+        let order = self.order.take().unwrap_or_else(|| self.evaluation_order());
+        for &position in order.iter() {
+            let id = self.nodes[position].id;
+            let sum = self.connections.iter()
+                .filter(|c| c.enabled && c.to == id)
+                .fold(0.0, |sum, c| sum + c.weight * self.values[self.find_node(c.from).unwrap()]);
+            self.values[position] = match self.nodes[position].kind {
+                NodeKind::Output => sum,
+                _ => self.nodes[position].activation.function().unwrap_or(activators::tanh)(sum),
+            };
+        }
+
+        self.order = Some(order);
+
+        for (o, value) in self.output_values.iter_mut().enumerate() {
+            *value = self.values[self.inputs + 1 + o];
+        }
+        &self.output_values
+    }
+
+    /// Positions of the hidden and output nodes, each after every node feeding it
+    fn evaluation_order(&self) -> Vec<usize> {
+        let mut pending: Vec<usize> = (self.inputs + 1..self.nodes.len()).collect();
+        let mut done: Vec<bool> = self.nodes.iter().map(|n| matches!(n.kind, NodeKind::Input | NodeKind::Bias)).collect();
+        let mut order = Vec::with_capacity(pending.len());
+
+        while !pending.is_empty() {
+            let ready: Vec<usize> = pending.iter().copied().filter(|position| {
+                let id = self.nodes[*position].id;
+                self.connections.iter()
+                    .filter(|c| c.enabled && c.to == id)
+                    .all(|c| done[self.find_node(c.from).unwrap()])
+            }).collect();
+
+            if ready.is_empty() {
+                // Only reachable through disabled links, the graph stays acyclic
+                order.extend(pending);
+                break;
+            }
+            ready.iter().for_each(|position| done[*position] = true);
+            pending.retain(|position| !done[*position]);
+            order.extend(ready);
+        }
+        order
+    }
+
+    /// True if a path of connections, enabled or not, leads from `from` to `to`
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut stack = vec![from];
+        let mut seen = vec![from];
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+            for c in self.connections.iter().filter(|c| c.from == node) {
+                if !seen.contains(&c.to) {
+                    seen.push(c.to);
+                    stack.push(c.to);
+                }
+            }
+        }
+        false
+    }
+
+    fn find_node(&self, id: usize) -> Option<usize> {
+        self.nodes.binary_search_by_key(&id, |n| n.id).ok()
+    }
+
+    fn find_connection(&self, innovation: usize) -> Option<&ConnectionGene> {
+        self.connections.binary_search_by_key(&innovation, |c| c.innovation).ok().map(|i| &self.connections[i])
+    }
+
+    fn insert_node(&mut self, node: NodeGene) {
+        let position = self.nodes.partition_point(|n| n.id < node.id);
+        self.nodes.insert(position, node);
+        self.values.insert(position, 0.0);
+        self.order = None;
+    }
+
+    fn insert_connection(&mut self, connection: ConnectionGene) {
+        let position = self.connections.partition_point(|c| c.innovation < connection.innovation);
+        self.connections.insert(position, connection);
+        self.order = None;
+    }
+}
+
+impl Display for NeatGenome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut genome_str = String::from("");
+        self.connections.iter().for_each(|c| {
+            genome_str.push_str(&format!(
+                "[{}] {} -> {}: {}{}\n",
+                c.innovation,
+                c.from,
+                c.to,
+                c.weight,
+                if c.enabled { "" } else { " (disabled)" }
+            ));
+        });
+
+        write!(f, "fitness: {}\nnodes: {}\n{}", self.fitness, self.nodes.len(), genome_str)
+    }
+}
+
+impl HasFitness for NeatGenome {
+    fn get_fitness(&self) -> f64 {
+        self.fitness
+    }
+}
+
+impl Network for NeatGenome {
+    fn calc(&mut self, x: &[f64]) -> &[f64] {
+        NeatGenome::calc(self, x)
+    }
+}
+
+impl Genome for NeatGenome {
+    type Config = NeatConfig;
+
+    fn spawn(architecture: &[usize], config: &NeatConfig) -> Self {
+        NeatGenome::new(architecture, config)
+    }
+
+    fn from_many_parents(parents: &[&Self], fitnesses: &[f64], operator: CrossoverOperator) -> Self {
+        NeatGenome::from_many_parents(parents, fitnesses, operator)
+    }
+
+    fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        NeatGenome::mutate_with(self, frequency, operator)
+    }
+
+    fn distance_with(&self, other: &Self, metric: DistanceMetric) -> f64 {
+        NeatGenome::distance_with(self, other, metric)
+    }
+
+    fn set_fitness(&mut self, ft: f64) {
+        NeatGenome::set_fitness(self, ft)
+    }
+
+    /// Inputs and outputs, the hidden topology is evolved
+    fn get_architecture(&self) -> Vec<usize> {
+        vec![self.inputs, self.outputs]
+    }
+
+    /// Innovation numbers line up the hidden nodes, only the inputs and outputs must match
+    fn crossover_compatible(&self, other: &Self) -> bool {
+        self.inputs == other.inputs && self.outputs == other.outputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConnectionGene, NeatConfig, NeatGenome, NodeGene, NodeKind};
+    use crate::{activators, evotrainer::{crossover::CrossoverOperator, speciation::DistanceMetric}};
+
+    /// Two inputs, bias, one output and one hidden node with the given (innovation, from, to, weight) genes
+    fn genome(genes: &[(usize, usize, usize, f64)]) -> NeatGenome {
+        let kinds = [NodeKind::Input, NodeKind::Input, NodeKind::Bias, NodeKind::Output, NodeKind::Hidden];
+        let nodes: Vec<NodeGene> = kinds.iter().enumerate()
+            .map(|(id, kind)| NodeGene { id, kind: *kind, activation: activators::Type::Identity })
+            .collect();
+        let connections = genes.iter()
+            .map(|&(innovation, from, to, weight)| ConnectionGene { innovation, from, to, weight, enabled: true })
+            .collect();
+
+        NeatGenome {
+            values: vec![0.0; nodes.len()],
+            output_values: vec![0.0; 1],
+            nodes,
+            connections,
+            inputs: 2,
+            outputs: 1,
+            config: NeatConfig::default(),
+            order: None,
+            fitness: 0.0,
+        }
+    }
+
+    /// Innovations 3 and 4 of `a` are disjoint, 5 and 6 of `b` are excess
+    fn parents() -> (NeatGenome, NeatGenome) {
+        let a = genome(&[(0, 0, 3, 1.0), (1, 1, 3, 1.0), (2, 2, 3, 1.0), (3, 0, 4, 0.5), (4, 4, 3, -0.5)]);
+        let b = genome(&[(0, 0, 3, 4.0), (1, 1, 3, 4.0), (2, 2, 3, 4.0), (5, 1, 4, 2.0), (6, 4, 3, 2.0)]);
+        (a, b)
+    }
+
+    #[test]
+    fn crossover_keeps_the_genes_of_the_fittest_parent() {
+        let (a, b) = parents();
+        let child = NeatGenome::from_many_parents(&[&b, &a], &[1.0, 2.0], CrossoverOperator::Centroid);
+
+        let innovations: Vec<usize> = child.get_connections().iter().map(|c| c.innovation).collect();
+        assert_eq!(innovations, vec![0, 1, 2, 3, 4]);
+
+        // Matching genes average the parents by their 1/3 and 2/3 rank shares,
+        // disjoint and excess genes come from the fittest parent unchanged
+        let weights: Vec<f64> = child.get_connections().iter().map(|c| c.weight).collect();
+        weights[..3].iter().for_each(|w| assert!((w - 2.0).abs() < 1e-12));
+        assert_eq!(weights[3..], [0.5, -0.5]);
+        assert!(child.get_connections().iter().all(|c| c.enabled));
+    }
+
+    #[test]
+    fn distance_counts_disjoint_and_excess_genes() {
+        let (a, b) = parents();
+        // c1 * 2 / 5 + c2 * 2 / 5 + c3 * |(3, 3, 3)| / 3
+        let expected = 2.0 / 5.0 + 2.0 / 5.0 + 0.4 * 27f64.sqrt() / 3.0;
+        assert!((a.distance_with(&b, DistanceMetric::Euclidean) - expected).abs() < 1e-12);
+        assert!((b.distance_with(&a, DistanceMetric::Euclidean) - expected).abs() < 1e-12);
+    }
+}
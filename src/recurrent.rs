@@ -0,0 +1,290 @@
+use std::fmt::Display;
+use rand::{rngs::StdRng, Rng};
+
+use crate::{activators, evotrainer::{crossover::CrossoverOperator, evotrainer::HasFitness, initialization::WeightInit, mutation::MutationOperator, speciation::DistanceMetric}, genome::{Genome, Network}};
+
+/// Context the hidden layers of a `RecurrentNet` receive besides their inputs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecurrentKind {
+    /// Each hidden layer sees its own activation of the previous step
+    #[default]
+    Elman,
+    /// Each hidden layer sees the network output of the previous step
+    Jordan,
+    /// Each hidden layer is a gated recurrent unit over its own previous
+    /// activation, with update and reset gates
+    Gru,
+}
+
+/// Weights per neuron, the input matrices start with the bias
+type Matrix = Vec<Vec<f64>>;
+
+#[derive(Clone)]
+struct RecurrentLayer {
+    /// Bias and layer input weights
+    input: Matrix,
+    /// Context weights, empty rows for the output layer
+    recurrent: Matrix,
+    /// (input, recurrent) weights of the GRU update and reset gates
+    gates: Vec<(Matrix, Matrix)>,
+    state: Vec<f64>,
+}
+
+impl RecurrentLayer {
+    fn new(amount: usize, input: usize, context: usize, gated: bool) -> RecurrentLayer {
+        let matrix = |columns: usize| -> Matrix {
+            (0..amount).map(|_| (0..columns).map(|_| 2f64 * rand::random::<f64>() - 1f64).collect()).collect()
+        };
+        RecurrentLayer {
+            input: matrix(input + 1),
+            recurrent: matrix(context),
+            gates: if gated { (0..2).map(|_| (matrix(input + 1), matrix(context))).collect() } else { Vec::new() },
+            state: vec![0.0; amount],
+        }
+    }
+
+    /// Every weight matrix of the layer, in the order of the flattened weights
+    fn matrices(&self) -> Vec<&Matrix> {
+        let mut matrices = vec![&self.input, &self.recurrent];
+        self.gates.iter().for_each(|(input, recurrent)| matrices.extend([input, recurrent]));
+        matrices
+    }
+
+    fn matrices_mut(&mut self) -> Vec<&mut Matrix> {
+        let mut matrices = vec![&mut self.input, &mut self.recurrent];
+        self.gates.iter_mut().for_each(|(input, recurrent)| matrices.extend([input, recurrent]));
+        matrices
+    }
+
+    /// Draws the layer and each gate with the scheme, context weights counting as extra inputs
+    fn initialize<R: Rng>(&mut self, scheme: WeightInit, rng: &mut R) {
+        let pairs = std::iter::once((&mut self.input, &mut self.recurrent))
+            .chain(self.gates.iter_mut().map(|(input, recurrent)| (input, recurrent)));
+        for (input, recurrent) in pairs {
+            let mut rows: Matrix = input.iter().zip(recurrent.iter()).map(|(i, r)| [i.as_slice(), r.as_slice()].concat()).collect();
+            scheme.initialize(&mut rows, rng);
+            for ((i, r), row) in input.iter_mut().zip(recurrent.iter_mut()).zip(rows) {
+                let (own, context) = row.split_at(i.len());
+                i.copy_from_slice(own);
+                r.copy_from_slice(context);
+            }
+        }
+    }
+}
+
+fn weighted_sum(weights: &[f64], values: &[f64]) -> f64 {
+    weights.iter().zip(values.iter()).fold(0.0, |sum, (w, v)| sum + w * v)
+}
+
+/// Layered network whose hidden layers keep a state across `calc` calls.
+/// Hidden layers are tanh and the output layer linear like `EvoNet`
+#[derive(Clone)]
+pub struct RecurrentNet {
+    layers: Vec<RecurrentLayer>,
+    kind: RecurrentKind,
+    /// Output of the last step
+    output: Vec<f64>,
+    fitness: f64,
+}
+
+impl RecurrentNet {
+    pub fn new(architecture: &[usize], kind: RecurrentKind) -> RecurrentNet {
+        let outputs = architecture[architecture.len() - 1];
+        let layers = (1..architecture.len()).map(|i| {
+            let hidden = i < architecture.len() - 1;
+            let context = match kind {
+                _ if !hidden => 0,
+                RecurrentKind::Elman | RecurrentKind::Gru => architecture[i],
+                RecurrentKind::Jordan => outputs,
+            };
+            RecurrentLayer::new(architecture[i], architecture[i - 1], context, hidden && kind == RecurrentKind::Gru)
+        }).collect();
+
+        RecurrentNet {
+            layers,
+            kind,
+            output: vec![0.0; outputs],
+            fitness: 0.0,
+        }
+    }
+
+    /// Recombines parents of the same architecture and kind into a child
+    pub fn from_many_parents(parents: &[&RecurrentNet], fitnesses: &[f64], operator: CrossoverOperator) -> RecurrentNet {
+        let weights: Vec<Vec<f64>> = parents.iter().map(|p| p.get_weights()).collect();
+        let mut nn = parents[0].clone();
+        nn.set_weights(&operator.recombine(&weights, fitnesses));
+        nn.fitness = 0.0;
+        nn.reset_state();
+        nn
+    }
+
+    pub fn get_kind(&self) -> RecurrentKind {
+        self.kind
+    }
+
+    /// Neuron count of each layer, starting with the inputs
+    pub fn get_architecture(&self) -> Vec<usize> {
+        let mut architecture = vec![self.layers[0].input[0].len() - 1];
+        self.layers.iter().for_each(|l| architecture.push(l.state.len()));
+        architecture
+    }
+
+    /// Amount of weights, including biases, gates and recurrent weights
+    pub fn weight_count(&self) -> usize {
+        self.layers.iter().fold(0, |sum, l| sum + l.matrices().iter().fold(0, |sum, m| sum + m.iter().fold(0, |sum, n| sum + n.len())))
+    }
+
+    /// Flattens the weights layer by layer, matrix by matrix, neuron by neuron
+    pub fn get_weights(&self) -> Vec<f64> {
+        let mut weights = Vec::with_capacity(self.weight_count());
+        self.layers.iter().for_each(|l| l.matrices().iter().for_each(|m| m.iter().for_each(|n| weights.extend_from_slice(n))));
+        weights
+    }
+
+    /// Overwrites the weights from a flat vector in the order of `get_weights`
+    pub fn set_weights(&mut self, weights: &[f64]) {
+        let mut position: usize = 0;
+        self.layers.iter_mut().for_each(|l| l.matrices_mut().into_iter().for_each(|m| m.iter_mut().for_each(|n| {
+            let len = n.len();
+            n.copy_from_slice(&weights[position..position + len]);
+            position += len;
+        })));
+    }
+
+    pub fn distance_with(&self, other: &RecurrentNet, metric: DistanceMetric) -> f64 {
+        metric.measure(&self.get_weights(), &other.get_weights())
+    }
+
+    pub fn set_fitness(&mut self, ft: f64) {
+        self.fitness = ft;
+    }
+
+    /// Perturbs each weight, recurrent and gate weights included,
+    /// with probability `frequency` using the given operator
+    pub fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        let mut weights = self.get_weights();
+        operator.mutate(&mut weights, frequency);
+        self.set_weights(&weights);
+    }
+
+    /// Redraws the weights layer by layer, `schemes[i]` for the i-th layer.
+    /// Layers past the end of `schemes` use `WeightInit::default()`
+    pub fn initialize_weights<R: Rng>(&mut self, schemes: &[WeightInit], rng: &mut R) {
+        self.layers.iter_mut().enumerate().for_each(|(i, l)| {
+            l.initialize(schemes.get(i).copied().unwrap_or_default(), rng);
+        });
+    }
+
+    /// Zeroes the hidden states and the remembered output
+    pub fn reset_state(&mut self) {
+        self.layers.iter_mut().for_each(|l| l.state.iter_mut().for_each(|s| *s = 0.0));
+        self.output.iter_mut().for_each(|o| *o = 0.0);
+    }
+
+    #[allow(non_snake_case)]
+    pub fn calc(&mut self, X: &[f64]) -> &[f64] {
+        let mut x = X.to_vec();
+        x.insert(0, 1f64);
+        let last = self.layers.len() - 1;
+
+        for j in 0..self.layers.len() {
+            let context = match self.kind {
+                RecurrentKind::Jordan => self.output.clone(),
+                _ => self.layers[j].state.clone(),
+            };
+            let layer = &mut self.layers[j];
+
+            let y: Vec<f64> = (0..layer.state.len()).map(|i| {
+                let sum = weighted_sum(&layer.input[i], &x);
+                if j == last {
+                    return sum;
+                }
+                if layer.gates.is_empty() {
+                    return activators::tanh(sum + weighted_sum(&layer.recurrent[i], &context));
+                }
+
+                let (update_input, update_recurrent) = &layer.gates[0];
+                let (reset_input, reset_recurrent) = &layer.gates[1];
+                let update = activators::sigm(weighted_sum(&update_input[i], &x) + weighted_sum(&update_recurrent[i], &context));
+                let reset = activators::sigm(weighted_sum(&reset_input[i], &x) + weighted_sum(&reset_recurrent[i], &context));
+                let reset_context: Vec<f64> = context.iter().map(|c| reset * c).collect();
+                let candidate = activators::tanh(sum + weighted_sum(&layer.recurrent[i], &reset_context));
+                (1.0 - update) * context[i] + update * candidate
+            }).collect();
+
+            layer.state.copy_from_slice(&y);
+            x = y;
+            x.insert(0, 1f64);
+        }
+
+        self.output.copy_from_slice(&self.layers[last].state);
+        &self.output
+    }
+}
+
+impl Display for RecurrentNet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut net_str = String::from("");
+        self.layers.iter().for_each(|l| {
+            net_str.push_str("layer\n");
+            l.matrices().iter().for_each(|m| m.iter().for_each(|n| {
+                let weights: Vec<String> = n.iter().map(|w| w.to_string()).collect();
+                net_str.push_str(&format!("[{}]\n", weights.join(", ")));
+            }));
+        });
+
+        write!(f, "fitness: {}\nkind: {:?}\n{}", self.fitness, self.kind, net_str)
+    }
+}
+
+impl HasFitness for RecurrentNet {
+    fn get_fitness(&self) -> f64 {
+        self.fitness
+    }
+}
+
+impl Network for RecurrentNet {
+    fn calc(&mut self, x: &[f64]) -> &[f64] {
+        RecurrentNet::calc(self, x)
+    }
+
+    fn reset_state(&mut self) {
+        RecurrentNet::reset_state(self)
+    }
+}
+
+impl Genome for RecurrentNet {
+    type Config = RecurrentKind;
+
+    fn spawn(architecture: &[usize], config: &RecurrentKind) -> Self {
+        RecurrentNet::new(architecture, *config)
+    }
+
+    fn from_many_parents(parents: &[&Self], fitnesses: &[f64], operator: CrossoverOperator) -> Self {
+        RecurrentNet::from_many_parents(parents, fitnesses, operator)
+    }
+
+    fn mutate_with(&mut self, frequency: f64, operator: MutationOperator) {
+        RecurrentNet::mutate_with(self, frequency, operator)
+    }
+
+    fn distance_with(&self, other: &Self, metric: DistanceMetric) -> f64 {
+        RecurrentNet::distance_with(self, other, metric)
+    }
+
+    fn set_fitness(&mut self, ft: f64) {
+        RecurrentNet::set_fitness(self, ft)
+    }
+
+    fn get_architecture(&self) -> Vec<usize> {
+        RecurrentNet::get_architecture(self)
+    }
+
+    fn weight_layer_count(architecture: &[usize], _config: &RecurrentKind) -> Option<usize> {
+        Some(architecture.len().saturating_sub(1))
+    }
+
+    fn initialize_weights(&mut self, schemes: &[WeightInit], rng: &mut StdRng) {
+        RecurrentNet::initialize_weights(self, schemes, rng)
+    }
+}